@@ -1,10 +1,11 @@
 use crate::*;
 use crate::{
-    collectors::{BlockCollector, ProposalCollector, VoteCollector},
+    collectors::{BlockCollector, EvidenceCollector, ProposalCollector, VoteCollector},
+    engine::BftAction,
     error::{handle_err, BftError, BftResult},
     objects::*,
     params::BftParams,
-    timer::{TimeoutInfo, WaitTimer},
+    timer::{TimeoutInfo, TimerKind, WaitTimer},
     utils::extract_two,
     wal::Wal,
 };
@@ -12,18 +13,13 @@ use crate::{
 use crossbeam::crossbeam_channel::{select, unbounded, Receiver, RecvError, Sender};
 #[allow(unused_imports)]
 use log::{debug, error, info, log};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::sync::Arc;
-use std::thread;
+use std::thread::{self, JoinHandle};
 use std::time::{Duration, Instant};
 
 pub(crate) const INIT_HEIGHT: Height = 0;
 pub(crate) const INIT_ROUND: Round = 0;
-pub(crate) const PROPOSAL_TIMES_COEF: u64 = 4;
-pub(crate) const TIMEOUT_RETRANSE_COEF: u32 = 15;
-
-#[cfg(feature = "verify_req")]
-const VERIFY_AWAIT_COEF: u32 = 50;
 
 /// BFT state message.
 pub struct Bft<T: BftSupport> {
@@ -32,6 +28,9 @@ pub struct Bft<T: BftSupport> {
     pub(crate) msg_receiver: Receiver<BftMsg>,
     pub(crate) timer_seter: Sender<TimeoutInfo>,
     pub(crate) timer_notity: Receiver<TimeoutInfo>,
+    pub(crate) action_sender: Option<Sender<BftAction>>,
+    pub(crate) event_senders: Vec<Sender<BftEvent>>,
+    pub(crate) commit_threads: Vec<JoinHandle<()>>,
     // bft-core params
     pub(crate) height: Height,
     pub(crate) round: Round,
@@ -50,10 +49,15 @@ pub struct Bft<T: BftSupport> {
     pub(crate) status: Option<Status>,
     pub(crate) verify_results: HashMap<Round, VerifyResp>,
     pub(crate) proof: Proof,
+    pub(crate) failed_proposers: BTreeMap<Height, Vec<Address>>,
+    pub(crate) election_states: BTreeMap<Height, Vec<u8>>,
     pub(crate) blocks: BlockCollector,
     pub(crate) proposals: ProposalCollector,
     pub(crate) votes: VoteCollector,
+    pub(crate) evidences: EvidenceCollector,
     pub(crate) wal_log: Wal,
+    // the node's own votes in the wal log being replayed
+    pub(crate) wal_votes: Vec<SignedVote>,
 
     // user define
     pub(crate) function: Arc<T>,
@@ -67,24 +71,28 @@ impl<T> Bft<T>
 where
     T: BftSupport + 'static,
 {
-    fn new(
+    pub(crate) fn new(
         s: Sender<BftMsg>,
         r: Receiver<BftMsg>,
         ts: Sender<TimeoutInfo>,
         tn: Receiver<TimeoutInfo>,
         f: Arc<T>,
-        local_address: Address,
-        wal_path: &str,
+        params: BftParams,
+        wal_log: Wal,
     ) -> Self {
         info!(
-            "Node {:?} initializing with wal_path: {}",
-            local_address, wal_path
+            "Node {:?} initializing with wal config: {:?}",
+            params.address, wal_log.config
         );
+        let cache_size = params.config.cache_size;
         Bft {
             msg_sender: s,
             msg_receiver: r,
             timer_seter: ts,
             timer_notity: tn,
+            action_sender: None,
+            event_senders: Vec::new(),
+            commit_threads: Vec::new(),
             height: INIT_HEIGHT,
             round: INIT_ROUND,
             step: Step::default(),
@@ -94,60 +102,64 @@ where
             round_filter: HashMap::new(),
             last_commit_round: None,
             last_commit_block_hash: None,
-            htime: Instant::now(),
-            params: BftParams::new(local_address),
+            htime: params.clock.now(),
+            params,
             feed: None,
             verify_results: HashMap::new(),
             proof: Proof::default(),
+            failed_proposers: BTreeMap::new(),
+            election_states: BTreeMap::new(),
             status: None,
             authority_manage: AuthorityManage::new(),
-            blocks: BlockCollector::new(),
-            proposals: ProposalCollector::new(),
-            votes: VoteCollector::new(),
-            wal_log: Wal::new(wal_path).unwrap(),
+            blocks: BlockCollector::new(cache_size),
+            proposals: ProposalCollector::new(cache_size),
+            votes: VoteCollector::new(cache_size),
+            evidences: EvidenceCollector::new(),
+            wal_log,
+            wal_votes: Vec::new(),
             function: f,
             consensus_power: false,
             is_byzantine: false,
         }
     }
 
-    /// A function to start a BFT state machine.
-    pub fn start(
+    /// A function to start a BFT state machine after the wal log is replayed,
+    /// return the handles of the timer thread and the main loop thread.
+    /// The main loop stops when it receives `BftMsg::Kill`, and then the timer stops.
+    pub(crate) fn start(
         s: Sender<BftMsg>,
         r: Receiver<BftMsg>,
         f: Arc<T>,
-        local_address: Address,
-        wal_path: &str,
-    ) {
+        wal_storage: Box<dyn WalStorage>,
+        params: BftParams,
+    ) -> BftResult<(JoinHandle<()>, JoinHandle<BftResult<()>>)> {
         // define message channel and timeout channel
         let (bft2timer, timer4bft) = unbounded();
         let (timer2bft, bft4timer) = unbounded();
+        let wal_log = Wal::new(wal_storage, params.config.wal, params.clock.clone())
+            .map_err(|e| BftError::LoadWalErr(format!("{:?}", e)))?;
+        let local_address = params.address.clone();
+        let clock = params.clock.clone();
 
-        let mut engine = Bft::new(
-            s,
-            r,
-            bft2timer,
-            bft4timer,
-            f,
-            local_address.clone(),
-            wal_path,
-        );
+        let mut engine = Bft::new(s, r, bft2timer, bft4timer, f, params, wal_log);
+
+        engine.load_wal_log()?;
 
         // start timer module.
-        let _timer_thread = thread::Builder::new()
+        let timer_thread = thread::Builder::new()
             .name("bft_timer".to_string())
             .spawn(move || {
-                let timer = WaitTimer::new(timer2bft, timer4bft);
+                let timer = WaitTimer::with_clock(timer2bft, timer4bft, clock);
                 timer.start();
             })
-            .unwrap_or_else(|_| panic!("Node {:?} starts time-thread failed!", local_address));
+            .map_err(|e| {
+                BftError::SpawnThreadErr(format!("{:?} of timer of node {:?}", e, local_address))
+            })?;
 
         // start main loop module.
-        let _main_thread = thread::Builder::new()
+        let main_thread = thread::Builder::new()
             .name("main_loop".to_string())
             .spawn(move || {
-                engine.load_wal_log();
-
                 loop {
                     let mut get_timer_msg = Err(RecvError);
                     let mut get_msg = Err(RecvError);
@@ -158,7 +170,7 @@ where
                     }
 
                     if let Ok(msg) = get_timer_msg {
-                        handle_err(engine.timeout_process(msg, true), &engine.params.address);
+                        handle_err(engine.process_timer(msg), &engine.params.address);
                     }
                     if let Ok(msg) = get_msg {
                         match msg {
@@ -170,73 +182,132 @@ where
                             }
                         }
                     }
+                    engine.set_wal_sync_timer();
                 }
+                engine.stop()
             })
-            .unwrap_or_else(|_| panic!("Node {:?} starts main-thread failed!", local_address));
+            .map_err(|e| {
+                BftError::SpawnThreadErr(format!(
+                    "{:?} of main loop of node {:?}",
+                    e, local_address
+                ))
+            })?;
+
+        Ok((timer_thread, main_thread))
+    }
+
+    /// A function to wait for the commit threads in flight and sync the wal log,
+    /// when the main loop stops.
+    fn stop(&mut self) -> BftResult<()> {
+        info!("Node {:?} stops bft process", self.params.address);
+        let panicked = self
+            .commit_threads
+            .drain(..)
+            .map(|handle| handle.join())
+            .filter(|result| result.is_err())
+            .count();
+        self.wal_log
+            .sync()
+            .map_err(|e| BftError::SaveWalErr(format!("{:?} of sync on stop", e)))?;
+        if panicked > 0 {
+            return Err(BftError::ShutdownErr(format!(
+                "{} commit threads panicked",
+                panicked
+            )));
+        }
+        Ok(())
     }
 
     pub(crate) fn process(&mut self, msg: BftMsg, need_wal: bool) -> BftResult<()> {
         match msg {
-            BftMsg::Proposal(encode) => {
-                if self.consensus_power {
-                    let (signed_proposal_encode, block) = extract_two(&encode)?;
-                    let signed_proposal: SignedProposal = rlp::decode(&signed_proposal_encode)
-                        .map_err(|e| {
-                            BftError::DecodeErr(format!("signed_proposal encounters {:?}", e))
-                        })?;
-                    debug!(
-                        "Node {:?} receives {:?}",
-                        self.params.address, &signed_proposal
-                    );
-                    self.check_and_save_proposal(
-                        &signed_proposal,
-                        &block.into(),
-                        &signed_proposal_encode,
-                        need_wal,
-                    )?;
-
-                    let proposal = signed_proposal.proposal;
-                    if self.step <= Step::ProposeWait {
-                        self.handle_proposal(&proposal)?;
-                        self.set_proposal(proposal);
-                        if self.step == Step::ProposeWait {
-                            self.transmit_prevote(false)?;
-                        }
+            BftMsg::Proposal(encode) if self.consensus_power => {
+                let (signed_proposal_encode, block) = extract_two(&encode)?;
+                let signed_proposal: SignedProposal =
+                    rlp::decode(signed_proposal_encode).map_err(|e| {
+                        BftError::DecodeErr(format!("signed_proposal encounters {:?}", e))
+                    })?;
+                debug!(
+                    "Node {:?} receives {:?}",
+                    self.params.address, &signed_proposal
+                );
+                let proposal = &signed_proposal.proposal;
+                self.emit(BftEvent::ProposalReceived {
+                    height: proposal.height,
+                    round: proposal.round,
+                    proposer: proposal.proposer.clone(),
+                    block_hash: proposal.block_hash.clone(),
+                });
+                let result = self.check_and_save_proposal(
+                    &signed_proposal,
+                    &block.into(),
+                    signed_proposal_encode,
+                    need_wal,
+                );
+                match &result {
+                    Ok(_) if proposal.height == self.height => {
+                        self.emit(BftEvent::ProposalAccepted {
+                            height: proposal.height,
+                            round: proposal.round,
+                            block_hash: proposal.block_hash.clone(),
+                        })
                     }
-                    // handle commit after proposal is ready while bft process blocked in Commit Step
-                    if self.step == Step::Commit {
-                        info!("Node {:?} receives lacking proposal in commit step", self.params.address);
+                    // a higher proposal is cached to be checked later
+                    Ok(_) | Err(BftError::HigherMsg(_)) => {}
+                    Err(e) => self.emit(BftEvent::ProposalRejected {
+                        height: proposal.height,
+                        round: proposal.round,
+                        reason: format!("{:?}", e),
+                    }),
+                }
+                // the locked block may come with a conflicting proposal in the commit step
+                if let Err(BftError::Equivocation(_)) = result {
+                    if self.is_commit_pending() {
                         self.handle_commit()?;
                     }
                 }
+                result?;
+
+                let proposal = signed_proposal.proposal;
+                if self.step <= Step::ProposeWait {
+                    self.handle_proposal(&proposal)?;
+                    self.set_proposal(proposal);
+                    if self.step == Step::ProposeWait {
+                        self.transmit_prevote(false)?;
+                    }
+                }
+                // handle commit after proposal is ready while bft process blocked in Commit Step
+                if self.step == Step::Commit {
+                    info!(
+                        "Node {:?} receives lacking proposal in commit step",
+                        self.params.address
+                    );
+                    self.handle_commit()?;
+                }
             }
 
-            BftMsg::Vote(encode) => {
-                if self.consensus_power {
-                    let signed_vote: SignedVote = rlp::decode(&encode).map_err(|e| {
-                        BftError::DecodeErr(format!("signed_vote encounters {:?}", e))
-                    })?;
-                    debug!("Node {:?} receives {:?}", self.params.address, signed_vote);
-                    self.check_and_save_vote(&signed_vote, need_wal)?;
-
-                    let vote = signed_vote.vote;
-                    match vote.vote_type {
-                        VoteType::Prevote => {
-                            if self.step <= Step::PrevoteWait {
-                                self.handle_vote(vote)?;
-                                if self.step >= Step::Prevote && self.check_prevote_count() {
-                                    self.change_to_step(Step::PrevoteWait);
-                                }
+            BftMsg::Vote(encode) if self.consensus_power => {
+                let signed_vote: SignedVote = rlp::decode(&encode)
+                    .map_err(|e| BftError::DecodeErr(format!("signed_vote encounters {:?}", e)))?;
+                debug!("Node {:?} receives {:?}", self.params.address, signed_vote);
+                self.check_and_save_vote(&signed_vote, need_wal)?;
+
+                let vote = signed_vote.vote;
+                match vote.vote_type {
+                    VoteType::Prevote => {
+                        if self.step <= Step::PrevoteWait {
+                            self.handle_vote(vote)?;
+                            if self.step >= Step::Prevote && self.check_prevote_count() {
+                                self.change_to_step(Step::PrevoteWait);
                             }
                         }
-                        VoteType::Precommit => {
-                            if self.step < Step::Precommit {
-                                self.handle_vote(vote.clone())?;
-                            }
-                            if self.step == Step::Precommit || self.step == Step::PrecommitWait {
-                                self.handle_vote(vote)?;
-                                self.handle_precommit()?;
-                            }
+                    }
+                    VoteType::Precommit => {
+                        if self.step < Step::Precommit {
+                            self.handle_vote(vote.clone())?;
+                        }
+                        if self.step == Step::Precommit || self.step == Step::PrecommitWait {
+                            self.handle_vote(vote)?;
+                            self.handle_precommit()?;
                         }
                     }
                 }
@@ -257,10 +328,16 @@ where
                 self.handle_status(status)?;
             }
 
-            #[cfg(feature = "verify_req")]
-            BftMsg::VerifyResp(verify_resp) => {
+            BftMsg::VerifyResp(verify_resp) if self.params.config.verify_req => {
                 debug!("Node {:?} receives {:?}", self.params.address, &verify_resp);
                 self.check_and_save_verify_resp(&verify_resp, need_wal)?;
+                if !verify_resp.is_pass {
+                    self.emit(BftEvent::ProposalRejected {
+                        height: self.height,
+                        round: verify_resp.round,
+                        reason: "the block fails the verification".to_string(),
+                    });
+                }
 
                 if self.step == Step::VerifyWait {
                     if self.check_verify() == VerifyResult::Undetermined {
@@ -269,6 +346,10 @@ where
                         self.transmit_precommit(false)?;
                     }
                 }
+                // the locked block is committed once it is verified
+                if self.is_commit_pending() {
+                    self.handle_commit()?;
+                }
             }
 
             BftMsg::Pause => {
@@ -289,6 +370,16 @@ where
                 self.clear(proof);
             }
 
+            BftMsg::QueryState(sender) => {
+                sender
+                    .send(self.consensus_state())
+                    .map_err(|e| BftError::SendMsgErr(format!("{:?} of consensus state", e)))?;
+            }
+
+            BftMsg::Subscribe(sender) => {
+                self.event_senders.push(sender);
+            }
+
             BftMsg::Corrupt => {
                 info!("Node {:?} is corrupt to be byzantine", self.params.address);
                 self.is_byzantine = true;
@@ -300,6 +391,17 @@ where
         Ok(())
     }
 
+    /// A function to process a timer of any kind.
+    pub(crate) fn process_timer(&mut self, tminfo: TimeoutInfo) -> BftResult<()> {
+        match tminfo.kind {
+            TimerKind::Step => self.timeout_process(tminfo, true),
+            TimerKind::WalSync => self
+                .wal_log
+                .sync_on_timer()
+                .map_err(|e| BftError::SaveWalErr(format!("{:?} of sync on timer", e))),
+        }
+    }
+
     pub(crate) fn timeout_process(&mut self, tminfo: TimeoutInfo, need_wal: bool) -> BftResult<()> {
         if tminfo.height < self.height {
             return Err(BftError::ObsoleteTimer(format!(
@@ -320,11 +422,17 @@ where
             )));
         }
 
+        self.emit(BftEvent::TimeoutFired {
+            height: tminfo.height,
+            round: tminfo.round,
+            step: tminfo.step,
+        });
+
         if need_wal && tminfo.step != Step::Prevote && tminfo.step != Step::Precommit {
             handle_err(
                 self.wal_log
                     .save(self.height, LogType::TimeOutInfo, &rlp::encode(&tminfo))
-                    .or_else(|e| Err(BftError::SaveWalErr(format!("{:?} of {:?}", e, &tminfo)))),
+                    .map_err(|e| BftError::SaveWalErr(format!("{:?} of {:?}", e, &tminfo))),
                 &self.params.address,
             );
         }
@@ -343,13 +451,11 @@ where
                     self.block_hash = None;
                 }
 
-                #[cfg(feature = "verify_req")]
+                if self.params.config.verify_req
+                    && self.check_verify() == VerifyResult::Undetermined
                 {
-                    let verify_result = self.check_verify();
-                    if verify_result == VerifyResult::Undetermined {
-                        self.change_to_step(Step::VerifyWait);
-                        return Ok(());
-                    }
+                    self.change_to_step(Step::VerifyWait);
+                    return Ok(());
                 }
 
                 self.transmit_precommit(false)?;
@@ -363,7 +469,6 @@ where
                 self.new_round_start(true)?;
             }
 
-            #[cfg(feature = "verify_req")]
             Step::VerifyWait => {
                 let signed_proposal = self
                     .proposals
@@ -392,8 +497,8 @@ where
         Ok(())
     }
 
-    fn handle_proposal(&self, proposal: &Proposal) -> BftResult<()> {
-        if proposal.height == self.height - 1 {
+    fn handle_proposal(&mut self, proposal: &Proposal) -> BftResult<()> {
+        if proposal.height + 1 == self.height {
             if self.last_commit_round.is_some() && proposal.round >= self.last_commit_round.unwrap()
             {
                 // deal with height fall behind one, round ge last commit round
@@ -414,14 +519,14 @@ where
     }
 
     fn handle_vote(&mut self, vote: Vote) -> BftResult<()> {
-        if vote.height == self.height - 1 {
+        if vote.height + 1 == self.height {
             if self.last_commit_round.is_some() && vote.round >= self.last_commit_round.unwrap() {
                 // deal with height fall behind one, round ge last commit round
                 let voter = vote.voter.clone();
                 let trans_flag = self.filter_height(&voter);
 
                 if trans_flag {
-                    self.height_filter.insert(voter, Instant::now());
+                    self.height_filter.insert(voter, self.params.clock.now());
                     self.retransmit_lower_votes(vote.round)?;
                 }
             }
@@ -431,7 +536,7 @@ where
             let trans_flag = self.filter_round(&voter);
 
             if trans_flag {
-                self.round_filter.insert(voter, Instant::now());
+                self.round_filter.insert(voter, self.params.clock.now());
                 self.retransmit_nil_precommit(&vote)?;
             }
         } else if vote.height == self.height && vote.round >= self.round {
@@ -460,6 +565,16 @@ where
         Ok(())
     }
 
+    /// Whether the node is in the commit step but has not committed its locked block.
+    fn is_commit_pending(&self) -> bool {
+        self.step == Step::Commit
+            && self
+                .lock_status
+                .as_ref()
+                .map(|lock_status| &lock_status.block_hash)
+                != self.last_commit_block_hash.as_ref()
+    }
+
     fn handle_commit(&mut self) -> BftResult<()> {
         let lock_status = self
             .lock_status
@@ -469,40 +584,60 @@ where
         let proof = self.generate_proof(lock_status.clone());
         self.set_proof(&proof);
 
+        let block_hash = lock_status.block_hash.clone();
+        // the proposal of the round may be lost or another one, so the locked block is taken
+        // from its proposal in any round of the height, whose proposer gets the commit
         let signed_proposal = self
             .proposals
-            .get_proposal(self.height, self.round)
+            .get_block_proposal(self.height, &block_hash)
             .ok_or_else(|| {
-                BftError::NotReady(
-                    "can not fetch proposal from cache when handle commit".to_string(),
-                )
+                BftError::NotReady(format!(
+                    "the proposal of the locked block {:?} is not received when commit",
+                    block_hash
+                ))
             })?;
         let proposal = signed_proposal.proposal;
-        #[cfg(not(feature = "compact_block"))]
-        let block = self
-            .blocks
-            .get_block(self.height, &proposal.block_hash)
-            .ok_or_else(|| {
-                BftError::ShouldNotHappen("can not fetch block from cache when commit".to_string())
-            })?
-            .clone();
-        #[cfg(feature = "compact_block")]
-        let block = self
+        // a conflicting proposal is kept after its block passes the check, while the proposal
+        // of a round may still wait for its verify result
+        let is_round_proposal = self
+            .proposals
+            .get_proposal(self.height, proposal.round)
+            .is_some_and(|signed_proposal| signed_proposal.proposal.block_hash == block_hash);
+        let verify_resp = self
             .verify_results
-            .get(&self.round)
-            .ok_or_else(|| {
-                BftError::ShouldNotHappen(
-                    "can not fetch complete block from cache when commit".to_string(),
-                )
-            })?
-            .clone()
-            .complete_block;
+            .get(&proposal.round)
+            .filter(|_| is_round_proposal);
+        if is_round_proposal && !verify_resp.is_some_and(|verify_resp| verify_resp.is_pass) {
+            return Err(BftError::NotReady(format!(
+                "the locked block {:?} is not verified when commit",
+                block_hash
+            )));
+        }
+        let block = if self.params.config.compact_block {
+            verify_resp
+                .and_then(|verify_resp| verify_resp.complete_block.clone())
+                .ok_or_else(|| {
+                    BftError::ShouldNotHappen(
+                        "can not fetch complete block from cache when commit".to_string(),
+                    )
+                })?
+        } else {
+            self.blocks
+                .get_block(self.height, &block_hash)
+                .ok_or_else(|| {
+                    BftError::ShouldNotHappen(
+                        "can not fetch block from cache when commit".to_string(),
+                    )
+                })?
+                .clone()
+        };
 
         let commit = Commit {
             height: self.height,
             block,
             proof,
             address: proposal.proposer.clone(),
+            evidences: self.evidences.get_commit_evidences(self.height),
         };
 
         info!(
@@ -510,13 +645,26 @@ where
             self.params.address,
             lock_status.block_hash,
             self.height,
-            Instant::now() - self.htime
+            self.params.clock.now() - self.htime
         );
 
+        self.emit(BftEvent::Commit {
+            height: self.height,
+            round: self.round,
+            block_hash: block_hash.clone(),
+        });
+        self.last_commit_round = Some(self.round);
+        self.last_commit_block_hash = Some(block_hash);
+        if self.action_sender.is_some() {
+            self.send_action(BftAction::Commit(commit));
+            return Ok(());
+        }
+
         let function = self.function.clone();
         let sender = self.msg_sender.clone();
         let address = self.params.address.clone();
-        thread::spawn(move || {
+        self.commit_threads.retain(|handle| !handle.is_finished());
+        let handle = thread::spawn(move || {
             handle_err(
                 function
                     .commit(commit)
@@ -529,9 +677,7 @@ where
                 &address,
             );
         });
-
-        self.last_commit_round = Some(self.round);
-        self.last_commit_block_hash = Some(proposal.block_hash);
+        self.commit_threads.push(handle);
         Ok(())
     }
 
@@ -545,20 +691,22 @@ where
         if status.height >= self.height {
             self.status = Some(status.clone());
 
-            #[cfg(not(feature = "machine_gun"))]
+            // the wal log of the next height is opened after the commit wait, so a status
+            // replayed from it goes to the new height at once
+            if !self.params.config.machine_gun
+                && status.height == self.height
+                && self.wal_log.height() <= status.height
             {
-                if status.height == self.height {
-                    let cost_time = Instant::now() - self.htime;
-                    let interval = self.params.timer.get_total_duration();
-                    let tv = if cost_time < interval {
-                        interval - cost_time
-                    } else {
-                        Duration::new(0, 0)
-                    };
-                    self.change_to_step(Step::CommitWait);
-                    self.set_timer(tv, Step::CommitWait);
-                    return Ok(());
-                }
+                let cost_time = self.params.clock.now() - self.htime;
+                let interval = self.params.timer.get_total_duration();
+                let tv = if cost_time < interval {
+                    interval - cost_time
+                } else {
+                    Duration::new(0, 0)
+                };
+                self.change_to_step(Step::CommitWait);
+                self.set_timer(tv, Step::CommitWait);
+                return Ok(());
             }
 
             if status.height > self.height {
@@ -587,18 +735,25 @@ where
             return self.transmit_byzantine_proposal();
         }
 
+        // never sign a conflicting proposal in the same round, even after restart
+        if let Some(signed_proposal) = self.get_own_proposal() {
+            debug!(
+                "Node {:?} transmits the signed proposal again at h:{}, r:{}",
+                self.params.address, self.height, self.round
+            );
+            self.block_hash = Some(signed_proposal.proposal.block_hash.clone());
+            let msg = BftMsg::Proposal(self.build_signed_proposal_encode(&signed_proposal)?);
+            self.transmit(msg.clone());
+            self.send_bft_msg(msg)?;
+            return Ok(());
+        }
+
         if self.lock_status.is_none()
             && (self.feed.is_none() || self.proof.height != self.height - 1)
         {
             // if a proposer find there is no proposal nor lock, goto step proposewait
-            let coef = if self.round > PROPOSAL_TIMES_COEF {
-                PROPOSAL_TIMES_COEF
-            } else {
-                self.round
-            };
-
             self.set_timer(
-                self.params.timer.get_propose() * 2u32.pow(coef as u32),
+                self.params.timer.get_propose_wait(self.round),
                 Step::ProposeWait,
             );
             return Err(BftError::NotReady(format!(
@@ -607,7 +762,7 @@ where
             )));
         }
 
-        let msg = if self.lock_status.is_some() {
+        let proposal = if self.lock_status.is_some() {
             // if is locked, boradcast the lock proposal
             debug!(
                 "Node {:?} is ready to transmit a locked proposal",
@@ -629,7 +784,7 @@ where
             let lock_proposal = lock_signed_proposal.proposal;
             let block_hash = lock_proposal.block_hash;
 
-            let proposal = Proposal {
+            Proposal {
                 height: self.height,
                 round: self.round,
                 block_hash,
@@ -637,9 +792,7 @@ where
                 lock_round: Some(lock_round),
                 lock_votes,
                 proposer: self.params.address.clone(),
-            };
-            let encode = self.build_signed_proposal_encode(&proposal)?;
-            BftMsg::Proposal(encode)
+            }
         } else {
             // if is not locked, transmit the cached proposal
             let block_hash = self.feed.clone().unwrap_or_else(|| {
@@ -654,7 +807,7 @@ where
                 self.params.address
             );
 
-            let proposal = Proposal {
+            Proposal {
                 height: self.height,
                 round: self.round,
                 block_hash,
@@ -662,15 +815,18 @@ where
                 lock_round: None,
                 lock_votes: Vec::new(),
                 proposer: self.params.address.clone(),
-            };
-            let encode = self.build_signed_proposal_encode(&proposal)?;
-            BftMsg::Proposal(encode)
+            }
         };
+        let signed_proposal = self.build_signed_proposal(&proposal)?;
+        let encode = self.build_signed_proposal_encode(&signed_proposal)?;
+        self.save_own_msg(self.height, LogType::ProposalBlock, &encode)?;
+        let msg = BftMsg::Proposal(encode);
+
         debug!(
             "Node {:?} transmits proposal at h:{}, r:{}",
             self.params.address, self.height, self.round
         );
-        self.function.transmit(msg.clone());
+        self.transmit(msg.clone());
         self.send_bft_msg(msg)?;
         Ok(())
     }
@@ -682,34 +838,38 @@ where
 
         let block_hash = if let Some(lock_status) = self.lock_status.clone() {
             lock_status.block_hash
-        } else if let Some(block_hash) = self.block_hash.clone() {
-            block_hash
         } else {
-            Hash::default()
+            self.block_hash.clone().unwrap_or_default()
         };
 
-        let vote = Vote {
-            vote_type: VoteType::Prevote,
-            height: self.height,
-            round: self.round,
-            block_hash: block_hash.clone(),
-            voter: self.params.address.clone(),
+        // never sign a conflicting prevote in the same round, even after restart
+        let signed_vote = match self.get_own_vote(self.height, self.round, VoteType::Prevote) {
+            Some(signed_vote) => signed_vote,
+            None => {
+                let vote = Vote {
+                    vote_type: VoteType::Prevote,
+                    height: self.height,
+                    round: self.round,
+                    block_hash,
+                    voter: self.params.address.clone(),
+                };
+                self.sign_own_vote(&vote)?
+            }
         };
-        let signed_vote = self.build_signed_vote(&vote)?;
         let msg = BftMsg::Vote(rlp::encode(&signed_vote));
 
         debug!(
             "Node {:?} prevotes to {:?} at h:{} r:{}",
-            self.params.address, block_hash, self.height, self.round
+            self.params.address, signed_vote.vote.block_hash, self.height, self.round
         );
-        self.function.transmit(msg.clone());
+        self.transmit(msg.clone());
         if !resend {
             self.change_to_step(Step::Prevote);
             handle_err(self.send_bft_msg(msg), &self.params.address);
         }
 
         self.set_timer(
-            self.params.timer.get_prevote() * TIMEOUT_RETRANSE_COEF,
+            self.params.timer.get_prevote() * self.params.config.retransmit_coef,
             Step::Prevote,
         );
 
@@ -728,34 +888,40 @@ where
             Hash::default()
         };
 
-        let vote = Vote {
-            vote_type: VoteType::Precommit,
-            height: self.height,
-            round: self.round,
-            block_hash: block_hash.clone(),
-            voter: self.params.address.clone(),
+        // never sign a conflicting precommit in the same round, even after restart
+        let signed_vote = match self.get_own_vote(self.height, self.round, VoteType::Precommit) {
+            Some(signed_vote) => signed_vote,
+            None => {
+                let vote = Vote {
+                    vote_type: VoteType::Precommit,
+                    height: self.height,
+                    round: self.round,
+                    block_hash,
+                    voter: self.params.address.clone(),
+                };
+                self.sign_own_vote(&vote)?
+            }
         };
-        let signed_vote = self.build_signed_vote(&vote)?;
         let msg = BftMsg::Vote(rlp::encode(&signed_vote));
 
         debug!(
             "Node {:?} precommits to {:?} at h:{:?}, r:{:?}",
-            self.params.address, block_hash, self.height, self.round
+            self.params.address, signed_vote.vote.block_hash, self.height, self.round
         );
-        self.function.transmit(msg.clone());
+        self.transmit(msg.clone());
         if !resend {
             self.change_to_step(Step::Precommit);
             handle_err(self.send_bft_msg(msg), &self.params.address);
         }
 
         self.set_timer(
-            self.params.timer.get_precommit() * TIMEOUT_RETRANSE_COEF,
+            self.params.timer.get_precommit() * self.params.config.retransmit_coef,
             Step::Precommit,
         );
         Ok(())
     }
 
-    fn retransmit_lower_votes(&self, round: Round) -> BftResult<()> {
+    fn retransmit_lower_votes(&mut self, round: Round) -> BftResult<()> {
         if self.is_byzantine {
             return self.retransmit_byzantine_lower_votes();
         }
@@ -765,50 +931,71 @@ where
             self.params.address
         );
 
-        let prevote = Vote {
-            vote_type: VoteType::Prevote,
-            height: self.height - 1,
-            round,
-            block_hash: self.last_commit_block_hash.clone().unwrap(),
-            voter: self.params.address.clone(),
+        // retransmit the votes signed in the round if there are, never sign conflicting ones
+        let height = self.height - 1;
+        let signed_prevote = match self.get_own_vote(height, round, VoteType::Prevote) {
+            Some(signed_prevote) => signed_prevote,
+            None => {
+                let prevote = Vote {
+                    vote_type: VoteType::Prevote,
+                    height,
+                    round,
+                    block_hash: self.last_commit_block_hash.clone().unwrap(),
+                    voter: self.params.address.clone(),
+                };
+                self.sign_own_vote(&prevote)?
+            }
         };
-        let signed_prevote = self.build_signed_vote(&prevote)?;
-        self.function
-            .transmit(BftMsg::Vote(rlp::encode(&signed_prevote)));
-
-        let precommit = Vote {
-            vote_type: VoteType::Precommit,
-            height: self.height - 1,
-            round,
-            block_hash: self.last_commit_block_hash.clone().unwrap(),
-            voter: self.params.address.clone(),
+        self.transmit(BftMsg::Vote(rlp::encode(&signed_prevote)));
+
+        let signed_precommit = match self.get_own_vote(height, round, VoteType::Precommit) {
+            Some(signed_precommit) => signed_precommit,
+            None => {
+                let precommit = Vote {
+                    vote_type: VoteType::Precommit,
+                    height,
+                    round,
+                    block_hash: self.last_commit_block_hash.clone().unwrap(),
+                    voter: self.params.address.clone(),
+                };
+                self.sign_own_vote(&precommit)?
+            }
         };
-        let signed_precommit = self.build_signed_vote(&precommit)?;
-        self.function
-            .transmit(BftMsg::Vote(rlp::encode(&signed_precommit)));
+        self.transmit(BftMsg::Vote(rlp::encode(&signed_precommit)));
         Ok(())
     }
 
-    fn retransmit_nil_precommit(&self, vote: &Vote) -> BftResult<()> {
+    fn retransmit_nil_precommit(&mut self, vote: &Vote) -> BftResult<()> {
         if self.is_byzantine {
             return self.retransmit_byzantine_nil_precommit();
         }
 
-        let precommit = Vote {
-            vote_type: VoteType::Precommit,
-            height: vote.height,
-            round: vote.round,
-            block_hash: Hash::default(),
-            voter: self.params.address.clone(),
+        // only precommit nil if the node has not precommitted in the round
+        let signed_precommit = match self.get_own_vote(vote.height, vote.round, VoteType::Precommit)
+        {
+            Some(signed_precommit) => signed_precommit,
+            None => {
+                let precommit = Vote {
+                    vote_type: VoteType::Precommit,
+                    height: vote.height,
+                    round: vote.round,
+                    block_hash: Hash::default(),
+                    voter: self.params.address.clone(),
+                };
+                let signed_precommit = self.sign_own_vote(&precommit)?;
+                // keep it in the vote collector, so it is found when it is retransmitted again
+                let vote_weight = self.get_vote_weight(vote.height, &self.params.address);
+                let result = self.votes.add(&signed_precommit, vote_weight, self.height);
+                handle_err(result, &self.params.address);
+                signed_precommit
+            }
         };
-        let signed_precommit = self.build_signed_vote(&precommit)?;
 
         debug!(
-            "Node {:?} receives vote in lower round, retransmit nil precommit",
+            "Node {:?} receives vote in lower round, retransmit precommit",
             self.params.address
         );
-        self.function
-            .transmit(BftMsg::Vote(rlp::encode(&signed_precommit)));
+        self.transmit(BftMsg::Vote(rlp::encode(&signed_precommit)));
         Ok(())
     }
 
@@ -818,11 +1005,18 @@ where
                 "Node {:?} starts h:{}, r:{}",
                 self.params.address, self.height, self.round
             );
+            self.emit(BftEvent::NewRound {
+                height: self.height,
+                round: self.round,
+            });
         }
         self.change_to_step(Step::ProposeWait);
 
         if self.is_proposer()? {
-            if new_round {
+            if new_round && self.action_sender.is_some() {
+                self.clean_feed();
+                self.send_action(BftAction::GetBlock(self.height));
+            } else if new_round {
                 self.clean_feed();
                 let function = self.function.clone();
                 let sender = self.msg_sender.clone();
@@ -858,17 +1052,31 @@ where
     fn goto_new_height(&mut self, new_height: Height) {
         self.clean_save_info();
         self.clean_filter();
+        // keep the evidences of the last commit in case it is sent again
+        self.evidences.remove_below(new_height.saturating_sub(1));
+        let is_new_log = self.wal_log.height() < new_height;
         handle_err(
             self.wal_log
                 .set_height(new_height)
                 .map_err(|e| BftError::SaveWalErr(format!("{:?} of set_height", e))),
             &self.params.address,
         );
+        // only the wal log of the current height is loaded, carry the uncommitted evidences
+        if is_new_log {
+            for evidence in self.evidences.get_pending_evidences() {
+                handle_err(
+                    self.wal_log
+                        .save(new_height, LogType::Evidence, &rlp::encode(&evidence))
+                        .map_err(|e| BftError::SaveWalErr(format!("{:?} of {:?}", e, evidence))),
+                    &self.params.address,
+                );
+            }
+        }
 
         self.height = new_height;
         self.round = 0;
 
-        let now = Instant::now();
+        let now = self.params.clock.now();
         info!(
             "Node {:?} goto new height {}, last height costs {:?} to reach consensus",
             self.params.address,
@@ -876,6 +1084,7 @@ where
             now - self.htime
         );
         self.htime = now;
+        self.emit(BftEvent::NewHeight { height: new_height });
     }
 
     #[inline]
@@ -889,7 +1098,8 @@ where
     }
 
     fn is_proposer(&self) -> BftResult<bool> {
-        let proposer = self.get_proposer(self.height, self.round)?;
+        let proof = self.get_previous_proof(self.height);
+        let proposer = self.get_proposer(self.height, self.round, proof)?;
         debug!(
             "Node {:?} chooses proposer {:?} at h:{}, r:{}",
             self.params.address, proposer, self.height, self.round
@@ -904,14 +1114,8 @@ where
         }
 
         // if is not proposer, goto step proposewait
-        let coef = if self.round > PROPOSAL_TIMES_COEF {
-            PROPOSAL_TIMES_COEF
-        } else {
-            self.round
-        };
-
         self.set_timer(
-            self.params.timer.get_propose() * 2u32.pow(coef as u32),
+            self.params.timer.get_propose_wait(self.round),
             Step::ProposeWait,
         );
         Ok(false)
@@ -920,10 +1124,11 @@ where
     fn set_proposal(&mut self, proposal: Proposal) {
         let block_hash = proposal.block_hash;
 
-        if proposal.lock_round.is_some()
-            && (self.lock_status.is_none()
-                || self.lock_status.clone().unwrap().round <= proposal.lock_round.unwrap())
-        {
+        if let Some(lock_round) = proposal.lock_round.filter(|lock_round| {
+            self.lock_status
+                .as_ref()
+                .is_none_or(|lock_status| lock_status.round <= *lock_round)
+        }) {
             // receive a proposal with a later PoLC
             debug!(
                 "Node {:?} handles a proposal with a PoLC",
@@ -933,12 +1138,21 @@ where
             if self.round < proposal.round {
                 self.round_filter.clear();
                 self.round = proposal.round;
+                self.emit(BftEvent::NewRound {
+                    height: self.height,
+                    round: self.round,
+                });
             }
 
             self.block_hash = Some(block_hash.clone());
+            self.emit(BftEvent::LockSet {
+                height: self.height,
+                round: lock_round,
+                block_hash: block_hash.clone(),
+            });
             self.lock_status = Some(LockStatus {
                 block_hash,
-                round: proposal.lock_round.unwrap(),
+                round: lock_round,
                 votes: proposal.lock_votes,
             });
         } else if proposal.lock_round.is_none()
@@ -956,18 +1170,19 @@ where
                 "Node {:?} handles a proposal with an earlier PoLC",
                 self.params.address
             );
-            return;
         }
     }
 
     fn check_prevote_count(&mut self) -> bool {
         let mut flag = false;
+        let last_round = self.round;
         for (round, prevote_count) in self.votes.prevote_count.iter() {
             debug!(
                 "Node {:?} received {} prevotes in r:{}",
                 self.params.address, prevote_count, round
             );
-            if self.cal_above_threshold(*prevote_count) && *round >= self.round {
+            if self.cal_above_threshold(*prevote_count, &VoteType::Prevote) && *round >= self.round
+            {
                 flag = true;
                 if self.round < *round {
                     self.round_filter.clear();
@@ -975,6 +1190,12 @@ where
                 }
             }
         }
+        if self.round > last_round {
+            self.emit(BftEvent::NewRound {
+                height: self.height,
+                round: self.round,
+            });
+        }
         if !flag {
             return false;
         }
@@ -986,11 +1207,11 @@ where
             let mut tv = if self.cal_all_vote(prevote_set.count) {
                 Duration::new(0, 0)
             } else {
-                self.params.timer.get_prevote()
+                self.params.timer.get_prevote_wait(self.round)
             };
 
             for (hash, count) in &prevote_set.votes_by_proposal {
-                if self.cal_above_threshold(*count) {
+                if self.cal_above_threshold(*count, &VoteType::Prevote) {
                     if self.lock_status.is_some()
                         && self.lock_status.clone().unwrap().round < self.round
                     {
@@ -1009,7 +1230,7 @@ where
                     }
                     if self.lock_status.is_none() && !hash.0.is_empty() {
                         // receive a PoLC, lock the proposal
-                        self.set_polc(&hash, &prevote_set);
+                        self.set_polc(hash, &prevote_set);
                     }
                     tv = Duration::new(0, 0);
                     break;
@@ -1025,12 +1246,15 @@ where
 
     fn check_precommit_count(&mut self) -> PrecommitRes {
         let mut flag = false;
+        let last_round = self.round;
         for (round, precommit_count) in self.votes.precommit_count.iter() {
             debug!(
                 "Node {:?} received {} precommits in r:{}",
                 self.params.address, precommit_count, round
             );
-            if self.cal_above_threshold(*precommit_count) && *round >= self.round {
+            if self.cal_above_threshold(*precommit_count, &VoteType::Precommit)
+                && *round >= self.round
+            {
                 flag = true;
                 if self.round < *round {
                     self.round_filter.clear();
@@ -1038,6 +1262,12 @@ where
                 }
             }
         }
+        if self.round > last_round {
+            self.emit(BftEvent::NewRound {
+                height: self.height,
+                round: self.round,
+            });
+        }
         if !flag {
             return PrecommitRes::Below;
         }
@@ -1049,14 +1279,14 @@ where
             let tv = if self.cal_all_vote(precommit_set.count) {
                 Duration::new(0, 0)
             } else {
-                self.params.timer.get_precommit()
+                self.params.timer.get_precommit_wait(self.round)
             };
-            if !self.cal_above_threshold(precommit_set.count) {
+            if !self.cal_above_threshold(precommit_set.count, &VoteType::Precommit) {
                 return PrecommitRes::Below;
             }
 
             for (hash, count) in &precommit_set.votes_by_proposal {
-                if self.cal_above_threshold(*count) {
+                if self.cal_above_threshold(*count, &VoteType::Precommit) {
                     if hash.0.is_empty() {
                         debug!(
                             "Node {:?} reaches nil consensus, goto next round {:?}",
@@ -1065,7 +1295,7 @@ where
                         );
                         return PrecommitRes::Nil;
                     } else {
-                        self.set_polc(&hash, &precommit_set);
+                        self.set_polc(hash, &precommit_set);
                         return PrecommitRes::Proposal;
                     }
                 }
@@ -1077,7 +1307,6 @@ where
         PrecommitRes::Above
     }
 
-    #[cfg(feature = "verify_req")]
     fn check_verify(&mut self) -> VerifyResult {
         if let Some(lock_status) = self.lock_status.clone() {
             let round = lock_status.round;
@@ -1090,7 +1319,7 @@ where
                     return VerifyResult::Failed;
                 }
             } else {
-                let tv = self.params.timer.get_prevote() * VERIFY_AWAIT_COEF;
+                let tv = self.params.timer.get_prevote() * self.params.config.verify_await_coef;
                 self.set_timer(tv, Step::VerifyWait);
                 return VerifyResult::Undetermined;
             }
@@ -0,0 +1,319 @@
+use crate::*;
+use crate::{
+    election::{default_election, ProposerElection},
+    engine::{BftAction, BftEngine},
+    error::handle_err,
+    params::BftConfig,
+    timer::{GetInstant, TimeoutInfo},
+};
+use async_trait::async_trait;
+#[allow(unused_imports)]
+use log::{log, warn};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::time::Instant;
+use tokio::runtime::Handle;
+use tokio::sync::mpsc::{
+    unbounded_channel, UnboundedReceiver, UnboundedSender, WeakUnboundedSender,
+};
+use tokio::task;
+use tokio::time::{sleep_until, Instant as TokioInstant};
+
+/// Define the async version of `BftSupport`.
+/// The functions which may wait for other modules are async and run as separate tasks,
+/// the others should return quickly as they are called inside the state machine.
+#[async_trait]
+pub trait AsyncBftSupport: Sync + Send + 'static {
+    type Error: ::std::fmt::Debug + Send;
+    /// A user-defined function for block validation.
+    /// Every proposal bft received will call this function, even if the feed block.
+    /// Users should validate block format, block headers, transactions here.
+    /// The [`signed_proposal_hash`] is corresponding to the proposal of the [`block`].
+    async fn check_block(
+        &self,
+        block: &Block,
+        block_hash: &Hash,
+        signed_proposal_hash: &Hash,
+        height_round: (Height, Round),
+        is_lock: bool,
+        proposer: &Address,
+    ) -> Result<VerifyResp, Self::Error>;
+    /// A user-defined function for transmitting signed_proposals and signed_votes.
+    /// The signed_proposals and signed_votes have been serialized,
+    /// users do not have to care about the structure of SignedProposal and SignedVote.
+    fn transmit(&self, msg: BftMsg);
+    /// A user-defined function for processing the reaching-consensus block.
+    /// Users can execute the block and add it into chain.
+    async fn commit(&self, commit: Commit) -> Result<Status, Self::Error>;
+    /// A user-defined function for feeding the bft consensus.
+    /// The new block provided will feed for bft consensus of giving [`height`]
+    async fn get_block(&self, height: Height) -> Result<(Block, Hash), Self::Error>;
+    /// A user-defined function for signing a [`hash`].
+    /// Unlike the functions above it is not async: the state machine signs its own proposal
+    /// or vote in the middle of a step, saves it and counts it at once, so the step can not
+    /// wait for another task. It is called inside the state machine task and should return
+    /// quickly, e.g. by signing with a local key. A signer which waits for I/O, e.g. a remote
+    /// one, must not be blocked on with `block_on` here, since the runtime may have no other
+    /// thread to drive it.
+    fn sign(&self, hash: &Hash) -> Result<Signature, Self::Error>;
+    /// A user-defined function for checking a [`signature`].
+    fn check_sig(&self, signature: &Signature, hash: &Hash) -> Result<Address, Self::Error>;
+    /// A user-defined function for hashing a [`msg`].
+    fn crypt_hash(&self, msg: &[u8]) -> Hash;
+    /// A user-defined function for receiving a [`misbehavior`] of an authority,
+    /// users can slash, score peers or alert here.
+    fn report_misbehavior(&self, _misbehavior: Misbehavior) {}
+}
+
+/// An async Bft actuator, whose state machine runs as a tokio task.
+/// `commit`, `get_block` and `check_block` run as separate tasks, so the blocks are
+/// always verified as if `verify_req` is set, and the results are fed back as msgs.
+#[derive(Debug, Clone)]
+pub struct AsyncBftActuator(UnboundedSender<BftMsg>);
+
+impl AsyncBftActuator {
+    /// A function to create a new async Bft actuator with the config and spawn
+    /// the BFT state machine on the current tokio runtime.
+    pub fn new<T: AsyncBftSupport, S: WalStorage + 'static>(
+        support: Arc<T>,
+        address: Address,
+        storage: S,
+        config: BftConfig,
+    ) -> BftResult<Self> {
+        let election = default_election(&config);
+        Self::with_election(support, address, storage, config, election)
+    }
+
+    /// A function to create a new async Bft actuator with the config and the proposer election
+    /// and spawn the BFT state machine on the current tokio runtime.
+    pub fn with_election<T: AsyncBftSupport, S: WalStorage + 'static>(
+        support: Arc<T>,
+        address: Address,
+        storage: S,
+        config: BftConfig,
+        election: Arc<dyn ProposerElection>,
+    ) -> BftResult<Self> {
+        let handle = Handle::try_current().map_err(|e| {
+            BftError::SpawnThreadErr(format!("{:?} of async actuator of node {:?}", e, address))
+        })?;
+
+        let mut config = config;
+        config.verify_req = true;
+        let engine_support = EngineSupport {
+            support: support.clone(),
+        };
+        let engine = BftEngine::with_election(
+            Arc::new(engine_support),
+            address.clone(),
+            storage,
+            config,
+            Arc::new(SystemClock),
+            election,
+        )?;
+
+        let (sender, receiver) = unbounded_channel();
+        let main_loop = MainLoop {
+            engine,
+            support,
+            sender: sender.downgrade(),
+            receiver,
+            timers: BinaryHeap::new(),
+            address,
+        };
+        handle.spawn(main_loop.run());
+        Ok(AsyncBftActuator(sender))
+    }
+
+    /// A function for sending msg to the BFT state machine.
+    pub fn send(&self, msg: BftMsg) -> BftResult<()> {
+        let info = format!("{:?} by AsyncBftActuator", &msg);
+        self.0.send(msg).map_err(|_| BftError::SendMsgErr(info))
+    }
+}
+
+/// The error of the `BftSupport` called inside the state machine.
+#[derive(Debug)]
+enum EngineSupportError<E> {
+    /// An error of the async support.
+    Support(E),
+    /// The function runs as a task of the main loop, which the engine never calls.
+    NotInEngine,
+}
+
+/// The BftSupport called inside the state machine, which only calls the sync functions,
+/// as the async ones are returned as actions by the engine.
+struct EngineSupport<T> {
+    support: Arc<T>,
+}
+
+impl<T: AsyncBftSupport> BftSupport for EngineSupport<T> {
+    type Error = EngineSupportError<T::Error>;
+
+    fn check_block(
+        &self,
+        _block: &Block,
+        _block_hash: &Hash,
+        _signed_proposal_hash: &Hash,
+        _height_round: (Height, Round),
+        _is_lock: bool,
+        _proposer: &Address,
+    ) -> Result<VerifyResp, Self::Error> {
+        Err(EngineSupportError::NotInEngine)
+    }
+
+    fn transmit(&self, msg: BftMsg) {
+        self.support.transmit(msg);
+    }
+
+    fn commit(&self, _commit: Commit) -> Result<Status, Self::Error> {
+        Err(EngineSupportError::NotInEngine)
+    }
+
+    fn get_block(&self, _height: Height) -> Result<(Block, Hash), Self::Error> {
+        Err(EngineSupportError::NotInEngine)
+    }
+
+    fn sign(&self, hash: &Hash) -> Result<Signature, Self::Error> {
+        self.support.sign(hash).map_err(EngineSupportError::Support)
+    }
+
+    fn check_sig(&self, signature: &Signature, hash: &Hash) -> Result<Address, Self::Error> {
+        self.support
+            .check_sig(signature, hash)
+            .map_err(EngineSupportError::Support)
+    }
+
+    fn crypt_hash(&self, msg: &[u8]) -> Hash {
+        self.support.crypt_hash(msg)
+    }
+
+    fn report_misbehavior(&self, misbehavior: Misbehavior) {
+        self.support.report_misbehavior(misbehavior);
+    }
+}
+
+/// The task of the BFT state machine, which executes the actions of the step-driven engine.
+/// It stops when all the actuators are dropped or it receives `BftMsg::Kill`,
+/// then the wal log is synced.
+struct MainLoop<T: AsyncBftSupport> {
+    engine: BftEngine<EngineSupport<T>>,
+    support: Arc<T>,
+    sender: WeakUnboundedSender<BftMsg>,
+    receiver: UnboundedReceiver<BftMsg>,
+    timers: BinaryHeap<Reverse<TimeoutInfo>>,
+    address: Address,
+}
+
+impl<T: AsyncBftSupport> MainLoop<T> {
+    async fn run(mut self) {
+        let mut actions = self.engine.take_actions();
+        loop {
+            for action in actions.drain(..) {
+                self.execute(action);
+            }
+
+            let deadline = self
+                .timers
+                .peek()
+                .map(|Reverse(timeout)| timeout.get_instant());
+            actions = tokio::select! {
+                msg = self.receiver.recv() => match msg {
+                    Some(BftMsg::Kill) | None => break,
+                    Some(msg) => self.engine.handle_msg(msg),
+                },
+                _ = sleep_until(TokioInstant::from_std(deadline.unwrap_or_else(Instant::now))),
+                    if deadline.is_some() =>
+                {
+                    let Reverse(timeout) = self.timers.pop().unwrap();
+                    self.engine.handle_timeout(timeout)
+                }
+            };
+        }
+        handle_err(self.engine.stop(), &self.address);
+    }
+
+    fn execute(&mut self, action: BftAction) {
+        let sender = match action {
+            BftAction::Broadcast(msg) => return self.support.transmit(msg),
+            BftAction::SetTimer(timeout) => return self.timers.push(Reverse(timeout)),
+            _ => match self.sender.upgrade() {
+                Some(sender) => sender,
+                None => {
+                    // the actuators are all dropped, the main loop is stopping
+                    warn!(
+                        "Node {:?} drops {:?} as the main loop is stopping",
+                        self.address, action
+                    );
+                    return;
+                }
+            },
+        };
+        match action {
+            BftAction::Broadcast(_) | BftAction::SetTimer(_) => {}
+            BftAction::Commit(commit) => {
+                let support = self.support.clone();
+                let address = self.address.clone();
+                task::spawn(async move {
+                    let result = support
+                        .commit(commit)
+                        .await
+                        .map_err(|e| BftError::CommitFailed(format!("{:?}", e)))
+                        .and_then(|status| {
+                            sender
+                                .send(BftMsg::Status(status))
+                                .map_err(|e| BftError::SendMsgErr(format!("{:?}", e)))
+                        });
+                    handle_err(result, &address);
+                });
+            }
+            BftAction::GetBlock(height) => {
+                let support = self.support.clone();
+                let address = self.address.clone();
+                task::spawn(async move {
+                    let result = support
+                        .get_block(height)
+                        .await
+                        .map_err(|e| BftError::GetBlockFailed(format!("{:?}", e)))
+                        .and_then(|(block, block_hash)| {
+                            sender
+                                .send(BftMsg::Feed(Feed {
+                                    height,
+                                    block,
+                                    block_hash,
+                                }))
+                                .map_err(|e| BftError::SendMsgErr(format!("{:?}", e)))
+                        });
+                    handle_err(result, &address);
+                });
+            }
+            BftAction::CheckBlock(req) => {
+                let support = self.support.clone();
+                let address = self.address.clone();
+                task::spawn(async move {
+                    match support
+                        .check_block(
+                            &req.block,
+                            &req.block_hash,
+                            &req.signed_proposal_hash,
+                            (req.height, req.round),
+                            req.is_lock,
+                            &req.proposer,
+                        )
+                        .await
+                    {
+                        Ok(verify_resp) => handle_err(
+                            sender
+                                .send(BftMsg::VerifyResp(verify_resp))
+                                .map_err(|e| BftError::SendMsgErr(format!("{:?}", e))),
+                            &address,
+                        ),
+                        Err(e) => warn!(
+                            "Node {:?} encounters BftError::CheckTxsFailed({:?})",
+                            address, e
+                        ),
+                    }
+                });
+            }
+        }
+    }
+}
@@ -0,0 +1,77 @@
+//! An offline tool to print the records of a wal directory.
+use bft_rs::inspect::{inspect_wal_dir, WalDump};
+use std::env;
+use std::io::{self, Write};
+use std::process;
+
+const USAGE: &str = "Usage: bft-wal [--json] <wal_dir>";
+
+fn main() {
+    let mut json = false;
+    let mut dir = None;
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--json" => json = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ if dir.is_none() => dir = Some(arg),
+            _ => {
+                eprintln!("{}", USAGE);
+                process::exit(2);
+            }
+        }
+    }
+    let dir = dir.unwrap_or_else(|| {
+        eprintln!("{}", USAGE);
+        process::exit(2);
+    });
+
+    let dump = inspect_wal_dir(&dir).unwrap_or_else(|e| {
+        eprintln!("Read wal directory {} failed: {}", dir, e);
+        process::exit(1);
+    });
+
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let result = if json {
+        writeln!(out, "{:#}", dump.to_json())
+    } else {
+        print_dump(&mut out, &dump)
+    };
+    // stop quietly if the output is closed, e.g. piped into `head`
+    if let Err(e) = result {
+        if e.kind() != io::ErrorKind::BrokenPipe {
+            eprintln!("Print wal records failed: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+fn print_dump(out: &mut impl Write, dump: &WalDump) -> io::Result<()> {
+    match dump.current_height {
+        Some(height) => writeln!(out, "index height: {}", height)?,
+        None => writeln!(out, "index height: missing or corrupt")?,
+    }
+    for file in dump.files.iter() {
+        writeln!(out)?;
+        writeln!(
+            out,
+            "height {} ({} records)",
+            file.height,
+            file.records.len()
+        )?;
+        for record in file.records.iter() {
+            writeln!(
+                out,
+                "  @{:<8} {:<12} {}",
+                record.offset, record.log_type, record.content
+            )?;
+        }
+        if let Some(error) = &file.error {
+            writeln!(out, "  !! {}", error)?;
+        }
+    }
+    Ok(())
+}
@@ -1,8 +1,5 @@
 use crate::*;
-use crate::{
-    algorithm::{Bft, TIMEOUT_RETRANSE_COEF},
-    objects::*,
-};
+use crate::{algorithm::Bft, objects::*};
 use rand::prelude::*;
 
 impl<T> Bft<T>
@@ -29,7 +26,7 @@ where
             self.change_to_step(Step::Prevote);
         }
         self.set_timer(
-            self.params.timer.get_prevote() * TIMEOUT_RETRANSE_COEF,
+            self.params.timer.get_prevote() * self.params.config.retransmit_coef,
             Step::Prevote,
         );
         Ok(())
@@ -44,7 +41,7 @@ where
             self.change_to_step(Step::Precommit);
         }
         self.set_timer(
-            self.params.timer.get_precommit() * TIMEOUT_RETRANSE_COEF,
+            self.params.timer.get_precommit() * self.params.config.retransmit_coef,
             Step::Prevote,
         );
         Ok(())
@@ -73,8 +70,9 @@ where
             lock_votes: Vec::new(),
             proposer: self.params.address.clone(),
         };
-        let encode = self.build_signed_proposal_encode(&proposal)?;
-        self.function.transmit(BftMsg::Proposal(encode).clone());
+        let signed_proposal = self.build_signed_proposal(&proposal)?;
+        let encode = self.build_signed_proposal_encode(&signed_proposal)?;
+        self.transmit(BftMsg::Proposal(encode));
         Ok(())
     }
 
@@ -97,8 +95,7 @@ where
             vote: vote.clone(),
             signature,
         };
-        self.function
-            .transmit(BftMsg::Vote(rlp::encode(&signed_vote)));
+        self.transmit(BftMsg::Vote(rlp::encode(&signed_vote)));
         Ok(())
     }
 
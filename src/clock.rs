@@ -0,0 +1,85 @@
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// The real time the timer waits before checking a mock clock again.
+const MOCK_POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Define the source of time of the BFT state machine and the timer.
+pub trait Clock: Debug + Send + Sync {
+    /// A function to get the current time.
+    fn now(&self) -> Instant;
+
+    /// A function to get the real duration to wait before the deadline may be reached.
+    fn wait_duration(&self, deadline: Instant) -> Duration {
+        let now = self.now();
+        if deadline > now {
+            deadline - now
+        } else {
+            Duration::new(0, 0)
+        }
+    }
+}
+
+/// A clock following the system monotonic time.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// A clock which only moves when it is advanced manually.
+/// The clones share the same time, so a whole network can run in the same virtual time.
+#[derive(Debug, Clone)]
+pub struct MockClock {
+    base: Instant,
+    elapsed: Arc<Mutex<Duration>>,
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        MockClock {
+            base: Instant::now(),
+            elapsed: Arc::new(Mutex::new(Duration::new(0, 0))),
+        }
+    }
+}
+
+impl MockClock {
+    /// A function to create a new mock clock.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A function to move the clock forward.
+    pub fn advance(&self, duration: Duration) {
+        *self.elapsed.lock().unwrap() += duration;
+    }
+
+    /// A function to get the virtual time elapsed since the clock was created.
+    pub fn elapsed(&self) -> Duration {
+        *self.elapsed.lock().unwrap()
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        self.base + self.elapsed()
+    }
+
+    fn wait_duration(&self, deadline: Instant) -> Duration {
+        if deadline > self.now() {
+            MOCK_POLL_INTERVAL
+        } else {
+            Duration::new(0, 0)
+        }
+    }
+}
+
+/// A function to get the default clock.
+pub(crate) fn system_clock() -> Arc<dyn Clock> {
+    Arc::new(SystemClock)
+}
@@ -1,13 +1,11 @@
 use crate::objects::{SignedProposal, SignedVote, VoteType};
-use crate::{Address, Block, Hash, Height, Round};
+use crate::{Address, Block, Evidence, Hash, Height, Round};
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 use crate::error::{BftError, BftResult};
 use lru_cache::LruCache;
 
-pub(crate) const CACHE_N: u64 = 16;
-
 /// BFT vote collector
 #[derive(Debug, Clone)]
 pub(crate) struct VoteCollector {
@@ -16,15 +14,18 @@ pub(crate) struct VoteCollector {
     /// A HashMap to record prevote count of each round.
     pub(crate) prevote_count: HashMap<Round, u64>,
     pub(crate) precommit_count: HashMap<Round, u64>,
+    /// The number of heights and rounds cached.
+    cache_size: u64,
 }
 
 impl VoteCollector {
     /// A function to create a new BFT vote collector.
-    pub(crate) fn new() -> Self {
+    pub(crate) fn new(cache_size: u64) -> Self {
         VoteCollector {
-            votes: LruCache::new(CACHE_N as usize),
+            votes: LruCache::new(cache_size as usize),
             prevote_count: HashMap::new(),
             precommit_count: HashMap::new(),
+            cache_size,
         }
     }
 
@@ -52,7 +53,7 @@ impl VoteCollector {
                     *counter += vote_weight;
                 }
             } else {
-                let mut round_votes = RoundCollector::new();
+                let mut round_votes = RoundCollector::new(self.cache_size);
                 round_votes.add(signed_vote, vote_weight)?;
                 self.votes.insert(height, round_votes);
                 // update prevote count hashmap
@@ -72,7 +73,7 @@ impl VoteCollector {
                     *counter += vote_weight;
                 }
             } else {
-                let mut round_votes = RoundCollector::new();
+                let mut round_votes = RoundCollector::new(self.cache_size);
                 round_votes.add(signed_vote, vote_weight)?;
                 self.votes.insert(height, round_votes);
                 // update prevote count hashmap
@@ -118,9 +119,9 @@ pub(crate) struct RoundCollector {
 
 impl RoundCollector {
     /// A function to create a new round collector.
-    pub(crate) fn new() -> Self {
+    pub(crate) fn new(cache_size: u64) -> Self {
         RoundCollector {
-            round_votes: LruCache::new(CACHE_N as usize),
+            round_votes: LruCache::new(cache_size as usize),
         }
     }
 
@@ -206,7 +207,13 @@ impl VoteSet {
     /// A function to add a vote to the vote set.
     pub(crate) fn add(&mut self, signed_vote: &SignedVote, vote_weight: u64) -> BftResult<()> {
         let vote = &signed_vote.vote;
-        if self.votes_by_sender.contains_key(&vote.voter) {
+        if let Some(saved_vote) = self.votes_by_sender.get(&vote.voter) {
+            if saved_vote.vote.block_hash != vote.block_hash {
+                return Err(BftError::Equivocation(Box::new(Evidence::from_votes(
+                    saved_vote,
+                    signed_vote,
+                ))));
+            }
             return Err(BftError::RecvMsgAgain(format!("{:?}", signed_vote)));
         }
         self.votes_by_sender
@@ -236,12 +243,14 @@ impl VoteSet {
 #[derive(Debug)]
 pub(crate) struct ProposalCollector {
     pub proposals: LruCache<Height, ProposalRoundCollector>,
+    cache_size: u64,
 }
 
 impl ProposalCollector {
-    pub(crate) fn new() -> Self {
+    pub(crate) fn new(cache_size: u64) -> Self {
         ProposalCollector {
-            proposals: LruCache::new(CACHE_N as usize),
+            proposals: LruCache::new(cache_size as usize),
+            cache_size,
         }
     }
 
@@ -255,7 +264,7 @@ impl ProposalCollector {
                 .unwrap()
                 .add(round, signed_proposal)?
         } else {
-            let mut round_proposals = ProposalRoundCollector::new();
+            let mut round_proposals = ProposalRoundCollector::new(self.cache_size);
             round_proposals.add(round, signed_proposal)?;
             self.proposals.insert(height, round_proposals);
         }
@@ -268,6 +277,49 @@ impl ProposalCollector {
             .and_then(|prc| prc.get_proposal(round))
     }
 
+    /// A function to keep a conflicting proposal whose block has passed the check,
+    /// at most one in a round.
+    pub(crate) fn add_conflicting(&mut self, signed_proposal: &SignedProposal) -> bool {
+        let proposal = &signed_proposal.proposal;
+        self.proposals
+            .get_mut(&proposal.height)
+            .map(|prc| prc.add_conflicting(proposal.round, signed_proposal))
+            .unwrap_or(false)
+    }
+
+    pub(crate) fn has_conflicting(&mut self, height: Height, round: Round) -> bool {
+        self.proposals
+            .get_mut(&height)
+            .map(|prc| prc.conflicting_proposals.contains_key(&round))
+            .unwrap_or(false)
+    }
+
+    /// A function to get the proposal of the block in the earliest round of the height,
+    /// which is either the proposal of a round or a kept conflicting one.
+    pub(crate) fn get_block_proposal(
+        &mut self,
+        height: Height,
+        block_hash: &Hash,
+    ) -> Option<SignedProposal> {
+        self.proposals
+            .get_mut(&height)
+            .and_then(|prc| prc.get_block_proposal(block_hash))
+    }
+
+    /// A function to check whether the proposer has signed another proposal in the same round.
+    pub(crate) fn check_equivocation(&mut self, signed_proposal: &SignedProposal) -> BftResult<()> {
+        let proposal = &signed_proposal.proposal;
+        let height = proposal.height;
+        if !self.proposals.contains_key(&height) {
+            self.proposals
+                .insert(height, ProposalRoundCollector::new(self.cache_size));
+        }
+        self.proposals
+            .get_mut(&height)
+            .unwrap()
+            .check_equivocation(proposal.round, signed_proposal)
+    }
+
     pub(crate) fn remove(&mut self, height: Height, round: Round) -> Option<SignedProposal> {
         self.proposals
             .get_mut(&height)
@@ -278,12 +330,19 @@ impl ProposalCollector {
 #[derive(Clone, Debug)]
 pub(crate) struct ProposalRoundCollector {
     pub round_proposals: LruCache<Round, SignedProposal>,
+    /// The first signed proposal of each proposer in a round, whether it is valid or not.
+    pub signed_proposals: LruCache<Round, HashMap<Address, SignedProposal>>,
+    /// A conflicting proposal of a round whose block has passed the check,
+    /// since the others may lock its block.
+    pub conflicting_proposals: LruCache<Round, SignedProposal>,
 }
 
 impl ProposalRoundCollector {
-    pub(crate) fn new() -> Self {
+    pub(crate) fn new(cache_size: u64) -> Self {
         ProposalRoundCollector {
-            round_proposals: LruCache::new(CACHE_N as usize),
+            round_proposals: LruCache::new(cache_size as usize),
+            signed_proposals: LruCache::new(cache_size as usize),
+            conflicting_proposals: LruCache::new(cache_size as usize),
         }
     }
 
@@ -295,10 +354,56 @@ impl ProposalRoundCollector {
         Ok(())
     }
 
+    pub(crate) fn check_equivocation(
+        &mut self,
+        round: Round,
+        signed_proposal: &SignedProposal,
+    ) -> BftResult<()> {
+        let proposal = &signed_proposal.proposal;
+        if !self.signed_proposals.contains_key(&round) {
+            self.signed_proposals.insert(round, HashMap::new());
+        }
+        let proposals = self.signed_proposals.get_mut(&round).unwrap();
+        if let Some(saved_proposal) = proposals.get(&proposal.proposer) {
+            if saved_proposal.proposal != *proposal {
+                return Err(BftError::Equivocation(Box::new(Evidence::from_proposals(
+                    saved_proposal,
+                    signed_proposal,
+                ))));
+            }
+            return Ok(());
+        }
+        proposals.insert(proposal.proposer.clone(), signed_proposal.clone());
+        Ok(())
+    }
+
     pub(crate) fn get_proposal(&mut self, round: Round) -> Option<SignedProposal> {
         self.round_proposals.get_mut(&round).cloned()
     }
 
+    pub(crate) fn add_conflicting(
+        &mut self,
+        round: Round,
+        signed_proposal: &SignedProposal,
+    ) -> bool {
+        if self.conflicting_proposals.contains_key(&round) {
+            return false;
+        }
+        self.conflicting_proposals
+            .insert(round, signed_proposal.clone());
+        true
+    }
+
+    pub(crate) fn get_block_proposal(&mut self, block_hash: &Hash) -> Option<SignedProposal> {
+        self.round_proposals
+            .iter()
+            .chain(self.conflicting_proposals.iter())
+            .map(|(_, signed_proposal)| signed_proposal)
+            .filter(|signed_proposal| signed_proposal.proposal.block_hash == *block_hash)
+            .min_by_key(|signed_proposal| signed_proposal.proposal.round)
+            .cloned()
+    }
+
     pub(crate) fn remove(&mut self, round: Round) -> Option<SignedProposal> {
         self.round_proposals.remove(&round)
     }
@@ -309,9 +414,9 @@ pub(crate) struct BlockCollector {
 }
 
 impl BlockCollector {
-    pub(crate) fn new() -> Self {
+    pub(crate) fn new(cache_size: u64) -> Self {
         BlockCollector {
-            blocks: LruCache::new(CACHE_N as usize),
+            blocks: LruCache::new(cache_size as usize),
         }
     }
 
@@ -357,3 +462,76 @@ impl BlockSet {
         self.block_set.get(hash)
     }
 }
+
+/// BFT evidence collector.
+/// An evidence is pending until it is put in a commit, so that each evidence is committed once.
+#[derive(Debug, Default)]
+pub(crate) struct EvidenceCollector {
+    /// A BTreeMap that K is the height of the misbehavior, V is the evidences not committed yet.
+    pub(crate) pending: BTreeMap<Height, Vec<Evidence>>,
+    /// A BTreeMap that K is the commit height, V is the evidences put in the commit.
+    pub(crate) committed: BTreeMap<Height, Vec<Evidence>>,
+}
+
+impl EvidenceCollector {
+    /// A function to create a new evidence collector.
+    pub(crate) fn new() -> Self {
+        EvidenceCollector {
+            pending: BTreeMap::new(),
+            committed: BTreeMap::new(),
+        }
+    }
+
+    /// A function to add an evidence, return `false` if the same misbehavior has been recorded.
+    pub(crate) fn add(&mut self, evidence: &Evidence) -> bool {
+        let is_same = |e: &Evidence| {
+            e.evidence_type == evidence.evidence_type
+                && e.height == evidence.height
+                && e.round == evidence.round
+                && e.address == evidence.address
+        };
+        if self
+            .pending
+            .values()
+            .chain(self.committed.values())
+            .any(|evidences| evidences.iter().any(is_same))
+        {
+            return false;
+        }
+        self.pending
+            .entry(evidence.height)
+            .or_default()
+            .push(evidence.clone());
+        true
+    }
+
+    /// A function to get the evidences of the commit of the height.
+    /// The pending evidences not higher than the height are moved into the commit,
+    /// and the same evidences are returned if the commit is sent again.
+    pub(crate) fn get_commit_evidences(&mut self, height: Height) -> Vec<Evidence> {
+        if let Some(evidences) = self.committed.get(&height) {
+            return evidences.clone();
+        }
+        let higher = self.pending.split_off(&(height + 1));
+        let evidences: Vec<Evidence> = ::std::mem::replace(&mut self.pending, higher)
+            .into_values()
+            .flatten()
+            .collect();
+        self.committed.insert(height, evidences.clone());
+        evidences
+    }
+
+    /// A function to get all evidences not committed yet.
+    pub(crate) fn get_pending_evidences(&self) -> Vec<Evidence> {
+        self.pending
+            .values()
+            .flat_map(|evidences| evidences.iter().cloned())
+            .collect()
+    }
+
+    /// A function to forget the commits lower than the height,
+    /// the pending evidences are kept until they are committed.
+    pub(crate) fn remove_below(&mut self, height: Height) {
+        self.committed = self.committed.split_off(&height);
+    }
+}
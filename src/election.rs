@@ -0,0 +1,336 @@
+use crate::params::BftConfig;
+use crate::{Address, Height, Node, Proof, Round};
+
+use rand_core::{RngCore, SeedableRng};
+use rand_pcg::Pcg64Mcg as Pcg;
+use rlp::{Rlp, RlpStream};
+use std::fmt::Debug;
+use std::sync::Arc;
+
+/// Define how the proposer of a round is elected from the authorities.
+/// Every honest node must elect the same proposer, so the election must be deterministic.
+pub trait ProposerElection: Debug + Send + Sync {
+    /// A function to elect the proposer of the height and round,
+    /// which returns its index in the authority list.
+    /// The proof of the previous height is given if the node has got it.
+    fn elect(
+        &self,
+        height: Height,
+        round: Round,
+        authorities: &[Node],
+        proof: Option<&Proof>,
+    ) -> usize;
+
+    /// A function to elect the proposer with the state of the election of the height,
+    /// see `get_state`. It elects by `elect` by default.
+    fn elect_by_state(
+        &self,
+        height: Height,
+        round: Round,
+        authorities: &[Node],
+        proof: Option<&Proof>,
+        _state: &[u8],
+    ) -> usize {
+        self.elect(height, round, authorities, proof)
+    }
+
+    /// A function to get the state of the election of the height, e.g. the proposer priorities,
+    /// from the authorities of the height and the state of the previous height,
+    /// which is not given if the node has not got it, e.g. after a sync.
+    /// The node folds the state over the authority lists of the statuses and keeps it
+    /// in the wal log, so the state must be deterministic as well.
+    /// No state is kept by default.
+    fn get_state(
+        &self,
+        _height: Height,
+        _authorities: &[Node],
+        _previous_state: Option<&[u8]>,
+    ) -> Vec<u8> {
+        Vec::new()
+    }
+}
+
+/// An election choosing the proposer in turn, each node proposes
+/// as many heights in a row as its proposal weight.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TurnElection;
+
+impl ProposerElection for TurnElection {
+    fn elect(
+        &self,
+        height: Height,
+        round: Round,
+        authorities: &[Node],
+        _proof: Option<&Proof>,
+    ) -> usize {
+        let weight = get_proposal_weight(authorities);
+        let sum: u64 = weight.iter().sum();
+        let x = (height + round) % sum;
+
+        let mut acc = 0;
+        for (index, w) in weight.iter().enumerate() {
+            acc += *w;
+            if x < acc {
+                return index;
+            }
+        }
+        0
+    }
+}
+
+/// An election choosing the proposer randomly by the proposal weights,
+/// which is seeded by the height and round.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RandomElection;
+
+impl ProposerElection for RandomElection {
+    fn elect(
+        &self,
+        height: Height,
+        round: Round,
+        authorities: &[Node],
+        _proof: Option<&Proof>,
+    ) -> usize {
+        get_random_index(height + round, &get_proposal_weight(authorities))
+    }
+}
+
+/// An election choosing the proposer randomly by the proposal weights,
+/// which is seeded by the block hash and the height of the proof of the previous height
+/// and the round, so the proposers of a height are unknown until the previous block is committed.
+/// The precommit signatures are not used, since the nodes may collect different 2/3+ of them,
+/// while the block hash is the same in every valid proof.
+/// The proposer is chosen in turn if the proof is not given,
+/// the node can not propose without the proof anyway.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProofElection;
+
+impl ProposerElection for ProofElection {
+    fn elect(
+        &self,
+        height: Height,
+        round: Round,
+        authorities: &[Node],
+        proof: Option<&Proof>,
+    ) -> usize {
+        match proof {
+            Some(proof) => {
+                let mut data = proof.block_hash.to_vec();
+                data.extend_from_slice(&proof.height.to_be_bytes());
+                let seed = get_seed(&data).wrapping_add(round);
+                get_random_index(seed, &get_proposal_weight(authorities))
+            }
+            None => TurnElection.elect(height, round, authorities, proof),
+        }
+    }
+}
+
+/// A weighted round robin election in the style of the proposer priority of Tendermint.
+/// Every node gains its proposal weight of priority in each step, and the one with the highest
+/// priority proposes and loses the total weight, so the proposers are interleaved
+/// in proportion to their weights. A height takes a step, and a round takes a step more.
+///
+/// The priorities of a height are the state of the election, which are carried from the
+/// previous height to the authority list of the height, where a new node starts with a low
+/// priority. The priorities come back after the steps of the total weight if the authority
+/// list does not change, so they are rebuilt from the height at the heights of a multiple
+/// of the total weight. A node without the priorities of the previous height, e.g. after
+/// a sync, rebuilds them as well, and it elects as the others from the next such height.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PriorityElection;
+
+#[derive(Debug, Clone)]
+struct Priority {
+    node: Node,
+    priority: i64,
+}
+
+impl PriorityElection {
+    /// A function to create a new weighted round robin election.
+    pub fn new() -> Self {
+        PriorityElection
+    }
+
+    /// The priorities before the first step of the height.
+    fn get_priorities(
+        &self,
+        height: Height,
+        authorities: &[Node],
+        previous_state: Option<&[u8]>,
+    ) -> Vec<Priority> {
+        let total: u64 = get_proposal_weight(authorities).iter().sum();
+        let steps = if total == 0 { 0 } else { height % total };
+        match previous_state.and_then(decode_priorities) {
+            Some(carried) if steps > 0 => {
+                let mut priorities = carry_priorities(&carried, authorities);
+                step_priorities(&mut priorities);
+                priorities
+            }
+            _ => {
+                let mut priorities = carry_priorities(&[], authorities);
+                for _ in 0..steps {
+                    step_priorities(&mut priorities);
+                }
+                priorities
+            }
+        }
+    }
+}
+
+impl ProposerElection for PriorityElection {
+    fn elect(
+        &self,
+        height: Height,
+        round: Round,
+        authorities: &[Node],
+        proof: Option<&Proof>,
+    ) -> usize {
+        // the priorities are rebuilt from the height without the state
+        let state = self.get_state(height, authorities, None);
+        self.elect_by_state(height, round, authorities, proof, &state)
+    }
+
+    fn elect_by_state(
+        &self,
+        height: Height,
+        round: Round,
+        authorities: &[Node],
+        _proof: Option<&Proof>,
+        state: &[u8],
+    ) -> usize {
+        let mut priorities = match decode_priorities(state) {
+            Some(priorities) => carry_priorities(&priorities, authorities),
+            None => self.get_priorities(height, authorities, None),
+        };
+        let mut index = 0;
+        for _ in 0..=round {
+            index = step_priorities(&mut priorities);
+        }
+        index
+    }
+
+    fn get_state(
+        &self,
+        height: Height,
+        authorities: &[Node],
+        previous_state: Option<&[u8]>,
+    ) -> Vec<u8> {
+        let priorities = self.get_priorities(height, authorities, previous_state);
+        let mut stream = RlpStream::new_list(priorities.len());
+        for priority in priorities.iter() {
+            // the priority is encoded in two's complement
+            stream
+                .begin_list(2)
+                .append(&priority.node.address)
+                .append(&(priority.priority as u64));
+        }
+        stream.out()
+    }
+}
+
+fn decode_priorities(state: &[u8]) -> Option<Vec<(Address, i64)>> {
+    if state.is_empty() {
+        return None;
+    }
+    Rlp::new(state)
+        .iter()
+        .map(|item| Ok((item.val_at(0)?, item.val_at::<u64>(1)? as i64)))
+        .collect::<Result<_, rlp::DecoderError>>()
+        .ok()
+}
+
+fn get_total_weight(priorities: &[Priority]) -> u64 {
+    priorities
+        .iter()
+        .map(|priority| u64::from(priority.node.proposal_weight))
+        .sum()
+}
+
+fn carry_priorities(priorities: &[(Address, i64)], authorities: &[Node]) -> Vec<Priority> {
+    let total: u64 = get_proposal_weight(authorities).iter().sum();
+    authorities
+        .iter()
+        .map(|node| Priority {
+            node: node.clone(),
+            priority: priorities
+                .iter()
+                .find(|(address, _)| *address == node.address)
+                .map(|(_, priority)| *priority)
+                // a new node starts with -1.125 times the total weight
+                .unwrap_or_else(|| -((total + (total >> 3)) as i64)),
+        })
+        .collect()
+}
+
+/// Take a step of the priorities, return the index of the chosen node.
+fn step_priorities(priorities: &mut [Priority]) -> usize {
+    let total = get_total_weight(priorities) as i64;
+    if total == 0 {
+        return 0;
+    }
+
+    // keep the priorities in a window of twice the total weight and centered on 0
+    let max = priorities.iter().map(|p| p.priority).max().unwrap_or(0);
+    let min = priorities.iter().map(|p| p.priority).min().unwrap_or(0);
+    if max - min > 2 * total {
+        let ratio = (max - min + 2 * total - 1) / (2 * total);
+        priorities.iter_mut().for_each(|p| p.priority /= ratio);
+    }
+    let average = priorities.iter().map(|p| p.priority).sum::<i64>() / priorities.len() as i64;
+    priorities.iter_mut().for_each(|p| p.priority -= average);
+
+    priorities
+        .iter_mut()
+        .for_each(|p| p.priority += i64::from(p.node.proposal_weight));
+    // the first one of the highest priority is chosen
+    let mut index = 0;
+    for (i, p) in priorities.iter().enumerate() {
+        if p.priority > priorities[index].priority {
+            index = i;
+        }
+    }
+    priorities[index].priority -= total;
+    index
+}
+
+/// A function to get the built-in election chosen by `random_proposer` of the config.
+pub(crate) fn default_election(config: &BftConfig) -> Arc<dyn ProposerElection> {
+    if config.random_proposer {
+        Arc::new(RandomElection)
+    } else {
+        Arc::new(TurnElection)
+    }
+}
+
+fn get_proposal_weight(authorities: &[Node]) -> Vec<u64> {
+    authorities
+        .iter()
+        .map(|node| u64::from(node.proposal_weight))
+        .collect()
+}
+
+// FNV-1a, which is stable across platforms and versions, unlike the std hasher.
+fn get_seed(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf2_9ce4_8422_2325, |seed, byte| {
+        (seed ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+fn get_random_index(seed: u64, weight: &[u64]) -> usize {
+    let sum: u64 = weight.iter().sum();
+    let x = u64::MAX / sum;
+
+    let mut rng = Pcg::seed_from_u64(seed);
+    let mut res = rng.next_u64();
+    while res >= sum * x {
+        res = rng.next_u64();
+    }
+    let mut acc = 0;
+    for (index, w) in weight.iter().enumerate() {
+        acc += *w;
+        if res < acc * x {
+            return index;
+        }
+    }
+    0
+}
@@ -0,0 +1,158 @@
+use crate::*;
+use crate::{
+    algorithm::Bft,
+    election::{default_election, ProposerElection},
+    error::handle_err,
+    params::{BftConfig, BftParams},
+    timer::TimeoutInfo,
+    wal::{Wal, WalStorage},
+};
+use crossbeam::crossbeam_channel::{unbounded, Receiver};
+
+/// An outbound action of the step-driven engine, which should be executed by the caller.
+#[derive(Debug, Clone)]
+pub enum BftAction {
+    /// Broadcast the serialized signed_proposal or signed_vote to the other nodes.
+    Broadcast(BftMsg),
+    /// Execute the reaching-consensus block.
+    /// The status of the height should be fed back as `BftMsg::Status`.
+    Commit(Commit),
+    /// Feed the timeout back by `handle_timeout` when its instant is reached.
+    SetTimer(TimeoutInfo),
+    /// Get a new block to propose at the height.
+    /// The block should be fed back as `BftMsg::Feed`.
+    GetBlock(Height),
+    /// Verify the block of a proposal.
+    /// The result should be fed back as `BftMsg::VerifyResp`.
+    /// It is only produced if `verify_req` is set in the config.
+    CheckBlock(CheckBlockReq),
+}
+
+/// A request of block verification, the arguments of `BftSupport::check_block`.
+#[derive(Debug, Clone)]
+pub struct CheckBlockReq {
+    pub block: Block,
+    pub block_hash: Hash,
+    pub signed_proposal_hash: Hash,
+    pub height: Height,
+    pub round: Round,
+    pub is_lock: bool,
+    pub proposer: Address,
+}
+
+/// A BFT state machine driven step by step by the caller, without any thread.
+/// It runs the same state machine as `BftActuator`, but instead of calling
+/// `transmit`, `commit`, `get_block` (and `check_block` if `verify_req` is set)
+/// of the `BftSupport` and setting timers, it returns them as actions.
+/// The other functions of the `BftSupport` are still called synchronously.
+pub struct BftEngine<T: BftSupport> {
+    bft: Bft<T>,
+    action_receiver: Receiver<BftAction>,
+}
+
+impl<T> BftEngine<T>
+where
+    T: BftSupport + 'static,
+{
+    /// A function to create a new step-driven engine with the config and replay the wal log.
+    /// The actions produced by the replay are returned by the first call of
+    /// `handle_msg`, `handle_timeout` or `take_actions`.
+    pub fn new<S: WalStorage + 'static>(
+        support: Arc<T>,
+        address: Address,
+        storage: S,
+        config: BftConfig,
+        clock: Arc<dyn Clock>,
+    ) -> BftResult<Self> {
+        let election = default_election(&config);
+        Self::with_election(support, address, storage, config, clock, election)
+    }
+
+    /// A function to create a new step-driven engine with the config and the proposer election
+    /// and replay the wal log.
+    pub fn with_election<S: WalStorage + 'static>(
+        support: Arc<T>,
+        address: Address,
+        storage: S,
+        config: BftConfig,
+        clock: Arc<dyn Clock>,
+        election: Arc<dyn ProposerElection>,
+    ) -> BftResult<Self> {
+        let (msg_sender, msg_receiver) = unbounded();
+        let (timer_seter, timer_notity) = unbounded();
+        let (action_sender, action_receiver) = unbounded();
+        let wal_log = Wal::new(Box::new(storage), config.wal, clock.clone())
+            .map_err(|e| BftError::LoadWalErr(format!("{:?}", e)))?;
+
+        let mut bft = Bft::new(
+            msg_sender,
+            msg_receiver,
+            timer_seter,
+            timer_notity,
+            support,
+            BftParams::new(address, config, clock, election),
+            wal_log,
+        );
+        bft.action_sender = Some(action_sender);
+        bft.load_wal_log()?;
+        let mut engine = BftEngine {
+            bft,
+            action_receiver,
+        };
+        engine.process_internal_msgs();
+        Ok(engine)
+    }
+
+    /// A function to feed a msg to the state machine and get the actions it produces.
+    /// The errors are logged as the threaded state machine does.
+    pub fn handle_msg(&mut self, msg: BftMsg) -> Vec<BftAction> {
+        handle_err(self.bft.process(msg, true), &self.bft.params.address);
+        self.process_internal_msgs();
+        self.take_actions()
+    }
+
+    /// A function to feed a timeout set by `BftAction::SetTimer` to the state machine
+    /// and get the actions it produces.
+    pub fn handle_timeout(&mut self, timeout: TimeoutInfo) -> Vec<BftAction> {
+        handle_err(self.bft.process_timer(timeout), &self.bft.params.address);
+        self.process_internal_msgs();
+        self.take_actions()
+    }
+
+    /// A function to sync the wal log when the engine stops,
+    /// as the records of some durability modes are not synced yet.
+    pub fn stop(&mut self) -> BftResult<()> {
+        info!("Node {:?} stops bft engine", self.bft.params.address);
+        self.bft
+            .wal_log
+            .sync()
+            .map_err(|e| BftError::SaveWalErr(format!("{:?} of sync on stop", e)))
+    }
+
+    /// A function to get a snapshot of the state machine.
+    pub fn state(&self) -> ConsensusState {
+        self.bft.consensus_state()
+    }
+
+    /// A function to subscribe the events of the state machine.
+    /// The subscription ends when the receiver is dropped.
+    pub fn subscribe(&mut self) -> Receiver<BftEvent> {
+        let (sender, receiver) = unbounded();
+        self.bft.event_senders.push(sender);
+        receiver
+    }
+
+    /// A function to get the actions which have not been returned yet.
+    pub fn take_actions(&mut self) -> Vec<BftAction> {
+        self.action_receiver.try_iter().collect()
+    }
+
+    /// The state machine sends some msgs to itself, e.g. its own proposal
+    /// and the cached msgs of a new height, which are processed here at once.
+    fn process_internal_msgs(&mut self) {
+        while let Ok(msg) = self.bft.msg_receiver.try_recv() {
+            handle_err(self.bft.process(msg, true), &self.bft.params.address);
+        }
+        self.bft.set_wal_sync_timer();
+    }
+}
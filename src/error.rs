@@ -1,4 +1,4 @@
-use crate::Address;
+use crate::{Address, Evidence};
 #[allow(unused_imports)]
 use log::{error, log, trace, warn};
 
@@ -13,6 +13,8 @@ pub enum BftError {
     RecvMsgErr(String),
 
     RecvMsgAgain(String),
+    /// Receive conflicting messages signed by the same signer.
+    Equivocation(Box<Evidence>),
 
     ObsoleteMsg(String),
 
@@ -21,6 +23,12 @@ pub enum BftError {
     DecodeErr(String),
 
     SaveWalErr(String),
+    /// The wal log is corrupted or can not be read.
+    LoadWalErr(String),
+    /// The wal directory can not be created or used.
+    WalDirErr(String),
+    /// A thread of the state machine can not be spawned.
+    SpawnThreadErr(String),
 
     InvalidSender(String),
 
@@ -45,6 +53,10 @@ pub enum BftError {
     NotReady(String),
 
     ObsoleteTimer(String),
+    /// The state machine does not stop cleanly.
+    ShutdownErr(String),
+    /// The config is invalid.
+    InvalidConfig(String),
 }
 
 pub(crate) fn handle_err<T>(result: BftResult<T>, address: &Address) {
@@ -61,6 +73,7 @@ pub(crate) fn handle_err<T>(result: BftResult<T>, address: &Address) {
             | BftError::CheckSigFailed(_)
             | BftError::CheckTxFailed(_)
             | BftError::DecodeErr(_)
+            | BftError::Equivocation(_)
             | BftError::InvalidSender(_)
             | BftError::MismatchingBlock(_) => warn!("Node {:?} encounters {:?}", address, e),
 
@@ -69,8 +82,13 @@ pub(crate) fn handle_err<T>(result: BftResult<T>, address: &Address) {
             | BftError::RecvMsgErr(_)
             | BftError::CommitFailed(_)
             | BftError::SaveWalErr(_)
+            | BftError::LoadWalErr(_)
+            | BftError::WalDirErr(_)
+            | BftError::SpawnThreadErr(_)
             | BftError::SignFailed(_)
-            | BftError::GetBlockFailed(_) => error!("Node {:?} encounters {:?}", address, e),
+            | BftError::GetBlockFailed(_)
+            | BftError::ShutdownErr(_)
+            | BftError::InvalidConfig(_) => error!("Node {:?} encounters {:?}", address, e),
 
             BftError::ObsoleteTimer(_) => {}
         }
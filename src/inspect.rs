@@ -0,0 +1,306 @@
+use crate::*;
+use crate::{
+    objects::{LogType, SignedProposal, SignedVote, Vote},
+    timer::TimeoutInfo,
+    utils::{decode_block, decode_election_state, decode_failed_proposers, extract_two},
+    wal::{check_file_header, Wal, FILE_HEADER_LEN},
+};
+use serde_json::{json, Map, Value};
+use std::fs::{read, read_dir, read_to_string};
+use std::io;
+use std::time::Instant;
+
+/// A record read from a wal file.
+#[derive(Debug, Clone)]
+pub struct WalRecord {
+    /// the offset of the record in the file
+    pub offset: usize,
+    /// the type of the record
+    pub log_type: String,
+    /// the decoded record, or the error if it can not be decoded
+    pub content: Value,
+}
+
+/// The records of a wal file.
+#[derive(Debug, Clone)]
+pub struct WalFile {
+    /// the height of the file
+    pub height: Height,
+    /// the valid records
+    pub records: Vec<WalRecord>,
+    /// a torn tail or a corruption after the valid records
+    pub error: Option<String>,
+}
+
+/// The content of a wal directory.
+#[derive(Debug, Clone)]
+pub struct WalDump {
+    /// the height in the index file, if it is valid
+    pub current_height: Option<Height>,
+    /// the wal files sorted by height
+    pub files: Vec<WalFile>,
+}
+
+impl WalDump {
+    /// A function to convert the dump to a JSON value.
+    pub fn to_json(&self) -> Value {
+        let files: Vec<Value> = self
+            .files
+            .iter()
+            .map(|file| {
+                let records: Vec<Value> = file
+                    .records
+                    .iter()
+                    .map(|record| {
+                        json!({
+                            "offset": record.offset,
+                            "log_type": record.log_type,
+                            "content": record.content,
+                        })
+                    })
+                    .collect();
+                json!({
+                    "height": file.height,
+                    "records": records,
+                    "error": file.error,
+                })
+            })
+            .collect();
+        json!({
+            "current_height": self.current_height,
+            "files": files,
+        })
+    }
+}
+
+/// A function to read all records of a wal directory without modifying it.
+pub fn inspect_wal_dir(dir: &str) -> io::Result<WalDump> {
+    let current_height = read_to_string(format!("{}/index", dir))
+        .ok()
+        .and_then(|content| content.parse::<Height>().ok());
+
+    let mut heights = Vec::new();
+    for entry in read_dir(dir)? {
+        let path = entry?.path();
+        let file_height = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_suffix(".log"))
+            .and_then(|name| name.parse::<Height>().ok());
+        if let Some(file_height) = file_height {
+            heights.push(file_height);
+        }
+    }
+    heights.sort_unstable();
+
+    let mut files = Vec::with_capacity(heights.len());
+    for height in heights {
+        let path = format!("{}/{}.log", dir, height);
+        files.push(inspect_wal_file(&path, height)?);
+    }
+    Ok(WalDump {
+        current_height,
+        files,
+    })
+}
+
+fn inspect_wal_file(path: &str, height: Height) -> io::Result<WalFile> {
+    let buf = read(path)?;
+    let mut wal_file = WalFile {
+        height,
+        records: Vec::new(),
+        error: None,
+    };
+    if buf.len() < FILE_HEADER_LEN {
+        if !buf.is_empty() {
+            wal_file.error = Some(format!("torn header of {} bytes", buf.len()));
+        }
+        return Ok(wal_file);
+    }
+    if let Err(e) = check_file_header(&buf[..FILE_HEADER_LEN], path) {
+        wal_file.error = Some(e.to_string());
+        return Ok(wal_file);
+    }
+
+    let body = &buf[FILE_HEADER_LEN..];
+    let mut index = 0;
+    loop {
+        match Wal::decode_record(body, index, height) {
+            Ok(Some((mtype, body_start, body_end))) => {
+                let log_type = LogType::from(mtype);
+                let encode = &body[body_start..body_end];
+                wal_file.records.push(WalRecord {
+                    offset: FILE_HEADER_LEN + index,
+                    log_type: format!("{:?}", log_type),
+                    content: decode_record_content(log_type, encode)
+                        .unwrap_or_else(|e| json!({ "error": e })),
+                });
+                index = body_end;
+            }
+            Ok(None) => {
+                if index < body.len() {
+                    wal_file.error = Some(format!("torn tail of {} bytes", body.len() - index));
+                }
+                break;
+            }
+            Err(e) => {
+                wal_file.error = Some(e.to_string());
+                break;
+            }
+        }
+    }
+    Ok(wal_file)
+}
+
+fn decode_record_content(log_type: LogType, encode: &[u8]) -> Result<Value, String> {
+    let content = match log_type {
+        LogType::Proposal => {
+            let signed_proposal: SignedProposal =
+                rlp::decode(encode).map_err(|e| format!("{:?}", e))?;
+            signed_proposal_json(&signed_proposal)
+        }
+        LogType::ProposalBlock => {
+            let (signed_proposal_encode, block) =
+                extract_two(encode).map_err(|e| format!("{:?}", e))?;
+            let signed_proposal: SignedProposal =
+                rlp::decode(signed_proposal_encode).map_err(|e| format!("{:?}", e))?;
+            let mut content = signed_proposal_json(&signed_proposal);
+            content["block"] = Value::String(hex(block));
+            content
+        }
+        LogType::Vote => {
+            let signed_vote: SignedVote = rlp::decode(encode).map_err(|e| format!("{:?}", e))?;
+            signed_vote_json(&signed_vote)
+        }
+        LogType::Status => {
+            let status: Status = rlp::decode(encode).map_err(|e| format!("{:?}", e))?;
+            let authority_list: Vec<Value> = status
+                .authority_list
+                .iter()
+                .map(|node| {
+                    json!({
+                        "address": hex(&node.address),
+                        "proposal_weight": node.proposal_weight,
+                        "vote_weight": node.vote_weight,
+                    })
+                })
+                .collect();
+            json!({
+                "height": status.height,
+                "interval": status.interval,
+                "authority_list": authority_list,
+            })
+        }
+        LogType::Proof => {
+            let proof: Proof = rlp::decode(encode).map_err(|e| format!("{:?}", e))?;
+            proof_json(&proof)
+        }
+        LogType::Feed => {
+            let feed: Feed = rlp::decode(encode).map_err(|e| format!("{:?}", e))?;
+            json!({
+                "height": feed.height,
+                "block_hash": hex(&feed.block_hash),
+                "block": hex(&feed.block),
+            })
+        }
+        LogType::VerifyResp => {
+            let verify_resp: VerifyResp = rlp::decode(encode).map_err(|e| format!("{:?}", e))?;
+            json!({
+                "is_pass": verify_resp.is_pass,
+                "round": verify_resp.round,
+            })
+        }
+        LogType::TimeOutInfo => {
+            let timeout_info =
+                TimeoutInfo::decode(encode, Instant::now()).map_err(|e| format!("{:?}", e))?;
+            json!({
+                "height": timeout_info.height,
+                "round": timeout_info.round,
+                "step": format!("{:?}", timeout_info.step),
+                "duration": timeout_info.duration,
+            })
+        }
+        LogType::Block => {
+            let (height, block, block_hash) =
+                decode_block(encode).map_err(|e| format!("{:?}", e))?;
+            json!({
+                "height": height,
+                "block_hash": hex(&block_hash),
+                "block": hex(&block),
+            })
+        }
+        LogType::FailedProposers => {
+            let failed_proposers =
+                decode_failed_proposers(encode).map_err(|e| format!("{:?}", e))?;
+            let mut content = Map::new();
+            for (height, addresses) in failed_proposers.iter() {
+                let addresses = addresses.iter().map(|address| Value::String(hex(address)));
+                content.insert(height.to_string(), Value::Array(addresses.collect()));
+            }
+            Value::Object(content)
+        }
+        LogType::ElectionState => {
+            let (height, state) = decode_election_state(encode).map_err(|e| format!("{:?}", e))?;
+            json!({
+                "height": height,
+                "state": hex(&state),
+            })
+        }
+        LogType::Evidence => {
+            let evidence: Evidence = rlp::decode(encode).map_err(|e| format!("{:?}", e))?;
+            json!({
+                "evidence_type": format!("{:?}", evidence.evidence_type),
+                "height": evidence.height,
+                "round": evidence.round,
+                "address": hex(&evidence.address),
+                "first": hex(&evidence.first),
+                "second": hex(&evidence.second),
+            })
+        }
+    };
+    Ok(content)
+}
+
+fn signed_proposal_json(signed_proposal: &SignedProposal) -> Value {
+    let proposal = &signed_proposal.proposal;
+    let lock_votes: Vec<Value> = proposal.lock_votes.iter().map(signed_vote_json).collect();
+    json!({
+        "height": proposal.height,
+        "round": proposal.round,
+        "block_hash": hex(&proposal.block_hash),
+        "proof": proof_json(&proposal.proof),
+        "lock_round": proposal.lock_round,
+        "lock_votes": lock_votes,
+        "proposer": hex(&proposal.proposer),
+        "signature": hex(&signed_proposal.signature),
+    })
+}
+
+fn signed_vote_json(signed_vote: &SignedVote) -> Value {
+    let vote: &Vote = &signed_vote.vote;
+    json!({
+        "vote_type": format!("{:?}", vote.vote_type),
+        "height": vote.height,
+        "round": vote.round,
+        "block_hash": hex(&vote.block_hash),
+        "voter": hex(&vote.voter),
+        "signature": hex(&signed_vote.signature),
+    })
+}
+
+fn proof_json(proof: &Proof) -> Value {
+    let mut precommit_votes = Map::new();
+    for (address, signature) in proof.precommit_votes.iter() {
+        precommit_votes.insert(hex(address), Value::String(hex(signature)));
+    }
+    json!({
+        "height": proof.height,
+        "round": proof.round,
+        "block_hash": hex(&proof.block_hash),
+        "precommit_votes": precommit_votes,
+    })
+}
+
+fn hex(bytes: &[u8]) -> String {
+    format!("{}", HexFmt(bytes))
+}
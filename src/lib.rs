@@ -1,31 +1,47 @@
 //! An efficent and stable Rust library of BFT protocol for distributed system.
 use crate::{
     algorithm::Bft,
+    clock::{Clock, SystemClock},
+    election::{default_election, ProposerElection},
     error::{BftError, BftResult},
-    objects::{Vote, VoteType},
+    objects::{SignedProposal, SignedVote, Step, Vote, VoteType},
+    params::{BftConfig, BftParams, Quorum},
     utils::{get_total_weight, get_votes_weight},
+    wal::{FileWalStorage, WalStorage, DEFAULT_WAL_RETENTION},
 };
 
 use crate::utils::extract_two;
-use crossbeam::crossbeam_channel::{unbounded, Sender};
+use crossbeam::crossbeam_channel::{unbounded, Receiver, Sender};
 use hex_fmt::HexFmt;
 #[allow(unused_imports)]
 use log::{debug, error, info, log, trace};
 use rlp::{Decodable, DecoderError, Encodable, Prototype, Rlp, RlpStream};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt::{Debug, Formatter, Result as FmtResult};
 use std::hash::{Hash as Hashable, Hasher};
 use std::ops::Deref;
 use std::sync::Arc;
+use std::thread::JoinHandle;
 
 /// Define the core functions of the BFT state machine.
 pub mod algorithm;
+/// Define the async BftSupport and BftActuator, with the feature `async`.
+#[cfg(feature = "async")]
+pub mod asynchronous;
 /// Define simple byzantine behaviors.
 pub mod byzantine;
+/// Define the clock trait, a system clock and a mock clock.
+pub mod clock;
 /// Define collectors of blocks, signed_proposals and signed_votes.
 pub mod collectors;
+/// Define the proposer election trait and the built-in elections.
+pub mod election;
+/// Define the step-driven engine without threads.
+pub mod engine;
 /// Define errors.
 pub mod error;
+/// Define the offline wal inspection.
+pub mod inspect;
 /// Define structures only for this crate, including Proposal, Vote, Step.
 pub mod objects;
 /// Define params including time interval and local address.
@@ -119,26 +135,195 @@ pub type Height = u64;
 
 pub type Round = u64;
 
-pub struct BftActuator(Sender<BftMsg>);
+/// A Bft actuator, whose state machine runs in a main loop thread and a timer thread.
+/// The threads are stopped and joined by `shutdown` or when the actuator is dropped.
+pub struct BftActuator {
+    sender: Sender<BftMsg>,
+    timer_thread: Option<JoinHandle<()>>,
+    main_thread: Option<JoinHandle<BftResult<()>>>,
+}
 
 impl BftActuator {
     /// A function to create a new Bft actuator and start the BFT state machine.
-    pub fn new<T: BftSupport + 'static>(support: Arc<T>, address: Address, wal_path: &str) -> Self {
+    /// It returns an error if the wal log can not be loaded or the threads can not be spawned.
+    pub fn new<T: BftSupport + 'static>(
+        support: Arc<T>,
+        address: Address,
+        wal_path: &str,
+    ) -> BftResult<Self> {
+        Self::with_wal_durability(support, address, wal_path, WalDurability::default())
+    }
+
+    /// A function to create a new Bft actuator with the given wal durability policy
+    /// and start the BFT state machine.
+    pub fn with_wal_durability<T: BftSupport + 'static>(
+        support: Arc<T>,
+        address: Address,
+        wal_path: &str,
+        durability: WalDurability,
+    ) -> BftResult<Self> {
+        let storage = FileWalStorage::new(wal_path)
+            .map_err(|e| BftError::WalDirErr(format!("{:?} of {}", e, wal_path)))?;
+        let config = WalConfig {
+            durability,
+            ..WalConfig::default()
+        };
+        Self::with_wal_storage(support, address, storage, config)
+    }
+
+    /// A function to create a new Bft actuator with the given wal storage and wal config
+    /// and start the BFT state machine.
+    pub fn with_wal_storage<T: BftSupport + 'static, S: WalStorage + 'static>(
+        support: Arc<T>,
+        address: Address,
+        storage: S,
+        config: WalConfig,
+    ) -> BftResult<Self> {
+        let config = BftConfig::builder().wal_config(config).build()?;
+        Self::with_config(support, address, storage, config)
+    }
+
+    /// A function to create a new Bft actuator with the given wal storage and config
+    /// and start the BFT state machine.
+    pub fn with_config<T: BftSupport + 'static, S: WalStorage + 'static>(
+        support: Arc<T>,
+        address: Address,
+        storage: S,
+        config: BftConfig,
+    ) -> BftResult<Self> {
+        Self::with_clock(support, address, storage, config, Arc::new(SystemClock))
+    }
+
+    /// A function to create a new Bft actuator following the clock
+    /// and start the BFT state machine.
+    pub fn with_clock<T: BftSupport + 'static, S: WalStorage + 'static>(
+        support: Arc<T>,
+        address: Address,
+        storage: S,
+        config: BftConfig,
+        clock: Arc<dyn Clock>,
+    ) -> BftResult<Self> {
+        let election = default_election(&config);
+        Self::with_election(support, address, storage, config, clock, election)
+    }
+
+    /// A function to create a new Bft actuator following the clock and the proposer election
+    /// and start the BFT state machine.
+    pub fn with_election<T: BftSupport + 'static, S: WalStorage + 'static>(
+        support: Arc<T>,
+        address: Address,
+        storage: S,
+        config: BftConfig,
+        clock: Arc<dyn Clock>,
+        election: Arc<dyn ProposerElection>,
+    ) -> BftResult<Self> {
         let (sender, internal_receiver) = unbounded();
-        Bft::start(
+        let (timer_thread, main_thread) = Bft::start(
             sender.clone(),
             internal_receiver,
             support,
-            address,
-            wal_path,
-        );
-        BftActuator(sender)
+            Box::new(storage),
+            BftParams::new(address, config, clock, election),
+        )?;
+        Ok(BftActuator {
+            sender,
+            timer_thread: Some(timer_thread),
+            main_thread: Some(main_thread),
+        })
     }
 
     /// A function for sending msg to the BFT state machine.
     pub fn send(&self, msg: BftMsg) -> BftResult<()> {
         let info = format!("{:?} by BftActuator", &msg);
-        self.0.send(msg).map_err(|_| BftError::SendMsgErr(info))
+        self.sender
+            .send(msg)
+            .map_err(|_| BftError::SendMsgErr(info))
+    }
+
+    /// A function to stop the BFT state machine and wait for its threads.
+    /// The in-flight commits are waited for and the wal log is synced before the main loop exits.
+    /// It returns an error if the state machine does not stop cleanly,
+    /// and it does nothing if the state machine has been shut down.
+    pub fn shutdown(&mut self) -> BftResult<()> {
+        let main_thread = match self.main_thread.take() {
+            Some(main_thread) => main_thread,
+            None => return Ok(()),
+        };
+        // the main loop may have been stopped by a `BftMsg::Kill`
+        let _ = self.sender.send(BftMsg::Kill);
+        let result = main_thread
+            .join()
+            .map_err(|_| BftError::ShutdownErr("the main loop panicked".to_string()))
+            .and_then(|result| result);
+        // the timer stops once the main loop drops its channel
+        let timer_result = self
+            .timer_thread
+            .take()
+            .map_or(Ok(()), |timer_thread| timer_thread.join())
+            .map_err(|_| BftError::ShutdownErr("the timer panicked".to_string()));
+        result.and(timer_result)
+    }
+
+    /// A function to get a snapshot of the BFT state machine.
+    /// It waits until the state machine processes the msgs sent before.
+    pub fn query_state(&self) -> BftResult<ConsensusState> {
+        let (sender, receiver) = unbounded();
+        self.send(BftMsg::QueryState(sender))?;
+        receiver
+            .recv()
+            .map_err(|e| BftError::RecvMsgErr(format!("{:?} of consensus state", e)))
+    }
+
+    /// A function to subscribe the events of the BFT state machine.
+    /// The events are buffered until they are received,
+    /// and the subscription ends when the receiver is dropped.
+    pub fn subscribe(&self) -> BftResult<Receiver<BftEvent>> {
+        let (sender, receiver) = unbounded();
+        self.send(BftMsg::Subscribe(sender))?;
+        Ok(receiver)
+    }
+}
+
+impl Drop for BftActuator {
+    fn drop(&mut self) {
+        if let Err(e) = self.shutdown() {
+            error!("BftActuator shuts down with {:?}", e);
+        }
+    }
+}
+
+/// Define when the wal log is synced to the disk.
+/// Whatever the policy is (except `NoSync`), the wal log is synced before the node
+/// broadcasts its own signed_proposals and signed_votes.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
+pub enum WalDurability {
+    /// Sync after every record.
+    EveryRecord,
+    /// Sync when the node changes its step.
+    #[default]
+    StepTransition,
+    /// Sync the records in a group, at most once every given milliseconds.
+    GroupCommit(u64),
+    /// Never sync, only for tests.
+    NoSync,
+}
+
+/// Define the wal log config.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct WalConfig {
+    /// When the wal log is synced.
+    pub durability: WalDurability,
+    /// The number of heights whose logs are kept below the current height, at least 1
+    /// as the votes of the last height may be retransmitted.
+    pub retention: Height,
+}
+
+impl Default for WalConfig {
+    fn default() -> Self {
+        WalConfig {
+            durability: WalDurability::default(),
+            retention: DEFAULT_WAL_RETENTION,
+        }
     }
 }
 
@@ -153,12 +338,13 @@ pub enum BftMsg {
     Pause,
     Start,
     Clear(Proof),
+    QueryState(Sender<ConsensusState>),
+    Subscribe(Sender<BftEvent>),
 
     Kill,
     Corrupt,
 }
 
-#[cfg(feature = "verify_req")]
 #[derive(Clone, Eq, PartialEq)]
 pub enum VerifyResult {
     Approved,
@@ -178,41 +364,49 @@ pub struct Commit {
     pub proof: Proof,
     /// the proposer address
     pub address: Address,
+    /// the evidences of byzantine behaviors not committed before, each evidence is put in one commit.
+    /// A commit encoded before the evidences were added (a list of 4) decodes with none.
+    pub evidences: Vec<Evidence>,
 }
 
 impl Debug for Commit {
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
         write!(
             f,
-            "Commit {{ h: {}, addr: {:?}}}",
-            self.height, self.address,
+            "Commit {{ h: {}, addr: {:?}, evidences: {}}}",
+            self.height,
+            self.address,
+            self.evidences.len(),
         )
     }
 }
 
 impl Encodable for Commit {
     fn rlp_append(&self, s: &mut RlpStream) {
-        s.begin_list(4)
+        s.begin_list(5)
             .append(&self.height)
             .append(&self.block)
             .append(&self.proof)
-            .append(&self.address);
+            .append(&self.address)
+            .append_list(&self.evidences);
     }
 }
 
 impl Decodable for Commit {
     fn decode(r: &Rlp) -> Result<Self, DecoderError> {
         match r.prototype()? {
-            Prototype::List(4) => {
+            Prototype::List(len @ 4) | Prototype::List(len @ 5) => {
                 let height: Height = r.val_at(0)?;
                 let block: Block = r.val_at(1)?;
                 let proof: Proof = r.val_at(2)?;
                 let address: Address = r.val_at(3)?;
+                let evidences: Vec<Evidence> = if len == 5 { r.list_at(4)? } else { vec![] };
                 Ok(Commit {
                     height,
                     block,
                     proof,
                     address,
+                    evidences,
                 })
             }
             _ => Err(DecoderError::RlpInconsistentLengthAndData),
@@ -220,6 +414,103 @@ impl Decodable for Commit {
     }
 }
 
+/// A snapshot of the BFT state machine.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConsensusState {
+    /// the current height
+    pub height: Height,
+    /// the current round
+    pub round: Round,
+    /// the current step
+    pub step: Step,
+    /// the round of the lock, if there is a lock
+    pub lock_round: Option<Round>,
+    /// the block hash of the lock, if there is a lock
+    pub lock_hash: Option<Hash>,
+    /// the height of the latest proof
+    pub proof_height: Height,
+    /// the authority list of the current height
+    pub authority_list: Vec<Node>,
+    /// the weight of the prevotes and precommits received of each round at the current height
+    pub vote_weights: BTreeMap<Round, VoteWeight>,
+}
+
+/// The weight of the votes received in a round.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct VoteWeight {
+    /// the weight of prevotes
+    pub prevote: u64,
+    /// the weight of precommits
+    pub precommit: u64,
+}
+
+/// An event of the BFT state machine, emitted to the subscribers.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BftEvent {
+    /// The state machine goes to a new height.
+    NewHeight { height: Height },
+    /// The state machine starts a new round, or skips to a higher round.
+    NewRound { height: Height, round: Round },
+    /// The state machine changes its step.
+    StepChange {
+        height: Height,
+        round: Round,
+        step: Step,
+    },
+    /// A signed_proposal is received, before it is checked.
+    ProposalReceived {
+        height: Height,
+        round: Round,
+        proposer: Address,
+        block_hash: Hash,
+    },
+    /// A signed_proposal of the current height passes the checks.
+    ProposalAccepted {
+        height: Height,
+        round: Round,
+        block_hash: Hash,
+    },
+    /// A signed_proposal fails the checks or its block fails the verification.
+    ProposalRejected {
+        height: Height,
+        round: Round,
+        reason: String,
+    },
+    /// The state machine locks a block, by `set_polc` or a proposal with a later PoLC.
+    LockSet {
+        height: Height,
+        round: Round,
+        block_hash: Hash,
+    },
+    /// The state machine releases its lock.
+    LockReleased { height: Height, round: Round },
+    /// The quorum of prevotes on a block, or on nil if `block_hash` is none, is collected.
+    PrevoteQuorum {
+        height: Height,
+        round: Round,
+        block_hash: Option<Hash>,
+    },
+    /// The quorum of precommits on a block, or on nil if `block_hash` is none, is collected.
+    PrecommitQuorum {
+        height: Height,
+        round: Round,
+        block_hash: Option<Hash>,
+    },
+    /// The reaching-consensus block is handed over to commit,
+    /// it is emitted again if the commit is resent.
+    Commit {
+        height: Height,
+        round: Round,
+        block_hash: Hash,
+    },
+    /// A timer of the step fires.
+    TimeoutFired {
+        height: Height,
+        round: Round,
+        step: Step,
+    },
+}
+
 /// The status of a giving height.
 /// It should be served from outside after block execution.
 /// After receiving status of a specified height, the consensus of next height will start immediately.
@@ -312,9 +603,9 @@ pub struct VerifyResp {
     pub is_pass: bool,
     /// the round of proposal which contains the block
     pub round: Round,
-    #[cfg(feature = "compact_block")]
-    /// the block with complete transactions.
-    pub complete_block: Block,
+    /// the block with complete transactions, which is committed instead of the proposed block
+    /// if `compact_block` is set.
+    pub complete_block: Option<Block>,
 }
 
 impl Debug for VerifyResp {
@@ -327,29 +618,36 @@ impl Debug for VerifyResp {
     }
 }
 
+// The length of the list tells whether there is a complete block,
+// so the encoding is the same as the one of the former features.
 impl Encodable for VerifyResp {
     fn rlp_append(&self, s: &mut RlpStream) {
-        #[cfg(not(feature = "compact_block"))]
-        s.begin_list(2).append(&self.is_pass).append(&self.round);
-
-        #[cfg(feature = "compact_block")]
-        s.begin_list(3)
-            .append(&self.is_pass)
-            .append(&self.round)
-            .append(&self.complete_block);
+        match &self.complete_block {
+            None => {
+                s.begin_list(2).append(&self.is_pass).append(&self.round);
+            }
+            Some(complete_block) => {
+                s.begin_list(3)
+                    .append(&self.is_pass)
+                    .append(&self.round)
+                    .append(complete_block);
+            }
+        }
     }
 }
 
 impl Decodable for VerifyResp {
     fn decode(r: &Rlp) -> Result<Self, DecoderError> {
         match r.prototype()? {
-            #[cfg(not(feature = "compact_block"))]
             Prototype::List(2) => {
                 let is_pass: bool = r.val_at(0)?;
                 let round: Round = r.val_at(1)?;
-                Ok(VerifyResp { is_pass, round })
+                Ok(VerifyResp {
+                    is_pass,
+                    round,
+                    complete_block: None,
+                })
             }
-            #[cfg(feature = "compact_block")]
             Prototype::List(3) => {
                 let is_pass: bool = r.val_at(0)?;
                 let round: Round = r.val_at(1)?;
@@ -357,7 +655,7 @@ impl Decodable for VerifyResp {
                 Ok(VerifyResp {
                     is_pass,
                     round,
-                    complete_block,
+                    complete_block: Some(complete_block),
                 })
             }
             _ => Err(DecoderError::RlpInconsistentLengthAndData),
@@ -428,7 +726,7 @@ impl Node {
 }
 
 /// Proof
-#[derive(Clone, Eq, PartialEq)]
+#[derive(Clone, Eq, PartialEq, Default)]
 pub struct Proof {
     /// proof height
     pub height: Height,
@@ -450,17 +748,6 @@ impl Debug for Proof {
     }
 }
 
-impl Default for Proof {
-    fn default() -> Self {
-        Proof {
-            height: 0,
-            round: 0,
-            block_hash: Hash::default(),
-            precommit_votes: HashMap::new(),
-        }
-    }
-}
-
 impl Hashable for Proof {
     fn hash<H: Hasher>(&self, state: &mut H) {
         self.height.hash(state);
@@ -513,8 +800,7 @@ impl Decodable for Proof {
                     );
                     return Err(DecoderError::RlpIncorrectListLen);
                 }
-                let precommit_votes: HashMap<_, _> =
-                    key_list.into_iter().zip(value_list.into_iter()).collect();
+                let precommit_votes: HashMap<_, _> = key_list.into_iter().zip(value_list).collect();
                 Ok(Proof {
                     height,
                     round,
@@ -530,6 +816,162 @@ impl Decodable for Proof {
     }
 }
 
+/// The type of a byzantine behavior.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EvidenceType {
+    /// A voter signs two prevotes for different blocks in the same height and round.
+    DuplicatePrevote,
+    /// A voter signs two precommits for different blocks in the same height and round.
+    DuplicatePrecommit,
+    /// A proposer signs two different proposals in the same height and round.
+    DuplicateProposal,
+}
+
+impl From<u8> for EvidenceType {
+    fn from(s: u8) -> Self {
+        match s {
+            0 => EvidenceType::DuplicatePrevote,
+            1 => EvidenceType::DuplicatePrecommit,
+            2 => EvidenceType::DuplicateProposal,
+            _ => panic!("Invalid evidence type!"),
+        }
+    }
+}
+
+impl From<EvidenceType> for u8 {
+    fn from(s: EvidenceType) -> Self {
+        match s {
+            EvidenceType::DuplicatePrevote => 0,
+            EvidenceType::DuplicatePrecommit => 1,
+            EvidenceType::DuplicateProposal => 2,
+        }
+    }
+}
+
+/// Evidence of a byzantine behavior.
+/// It contains two conflicting signed messages from the same signer,
+/// which can be verified by [`check_evidence`].
+#[derive(Clone, PartialEq, Eq)]
+pub struct Evidence {
+    /// the type of the byzantine behavior
+    pub evidence_type: EvidenceType,
+    /// the height of the conflicting messages
+    pub height: Height,
+    /// the round of the conflicting messages
+    pub round: Round,
+    /// the address of the signer
+    pub address: Address,
+    /// the first encoded signed message, a signed_vote as in BftMsg::Vote or a signed_proposal without block
+    pub first: Vec<u8>,
+    /// the second encoded signed message, the same kind as the first one
+    pub second: Vec<u8>,
+}
+
+impl Evidence {
+    pub(crate) fn from_votes(first: &SignedVote, second: &SignedVote) -> Self {
+        let vote = &first.vote;
+        let evidence_type = match vote.vote_type {
+            VoteType::Prevote => EvidenceType::DuplicatePrevote,
+            VoteType::Precommit => EvidenceType::DuplicatePrecommit,
+        };
+        Evidence {
+            evidence_type,
+            height: vote.height,
+            round: vote.round,
+            address: vote.voter.clone(),
+            first: rlp::encode(first),
+            second: rlp::encode(second),
+        }
+    }
+
+    pub(crate) fn from_proposals(first: &SignedProposal, second: &SignedProposal) -> Self {
+        let proposal = &first.proposal;
+        Evidence {
+            evidence_type: EvidenceType::DuplicateProposal,
+            height: proposal.height,
+            round: proposal.round,
+            address: proposal.proposer.clone(),
+            first: rlp::encode(first),
+            second: rlp::encode(second),
+        }
+    }
+}
+
+impl Debug for Evidence {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(
+            f,
+            "Evidence {{ type: {:?}, h: {}, r: {}, addr: {:?}}}",
+            self.evidence_type, self.height, self.round, self.address,
+        )
+    }
+}
+
+impl Encodable for Evidence {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        let evidence_type: u8 = self.evidence_type.into();
+        s.begin_list(6)
+            .append(&evidence_type)
+            .append(&self.height)
+            .append(&self.round)
+            .append(&self.address)
+            .append(&self.first)
+            .append(&self.second);
+    }
+}
+
+impl Decodable for Evidence {
+    fn decode(r: &Rlp) -> Result<Self, DecoderError> {
+        match r.prototype()? {
+            Prototype::List(6) => {
+                let evidence_type: u8 = r.val_at(0)?;
+                if evidence_type > 2 {
+                    return Err(DecoderError::Custom("invalid evidence type"));
+                }
+                let evidence_type = EvidenceType::from(evidence_type);
+                let height: Height = r.val_at(1)?;
+                let round: Round = r.val_at(2)?;
+                let address: Address = r.val_at(3)?;
+                let first: Vec<u8> = r.val_at(4)?;
+                let second: Vec<u8> = r.val_at(5)?;
+                Ok(Evidence {
+                    evidence_type,
+                    height,
+                    round,
+                    address,
+                    first,
+                    second,
+                })
+            }
+            _ => Err(DecoderError::RlpInconsistentLengthAndData),
+        }
+    }
+}
+
+/// A misbehavior detected by the BFT state machine.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Misbehavior {
+    /// An authority signs conflicting messages, the evidence will also be delivered by Commit.
+    Equivocation(Evidence),
+    /// An authority signs a proposal with invalid lock votes.
+    InvalidLockVotes {
+        /// the address of the proposer
+        address: Address,
+        /// the encoded signed_proposal without block
+        signed_proposal: Vec<u8>,
+        /// the reason why the lock votes are invalid
+        reason: String,
+    },
+    /// A message claims to be signed by an authority, but the signature is invalid.
+    /// As anyone can forge such a message, it is a hint for peer scoring rather than slashing.
+    InvalidSignature {
+        /// the address of the claimed signer
+        address: Address,
+        /// the encoded signed_proposal without block or signed_vote
+        signed_msg: Vec<u8>,
+    },
+}
+
 /// User-defined functions.
 pub trait BftSupport: Sync + Send {
     type Error: ::std::fmt::Debug;
@@ -562,16 +1004,23 @@ pub trait BftSupport: Sync + Send {
     fn check_sig(&self, signature: &Signature, hash: &Hash) -> Result<Address, Self::Error>;
     /// A user-defined function for hashing a [`msg`].
     fn crypt_hash(&self, msg: &[u8]) -> Hash;
+    /// A user-defined function for receiving a [`misbehavior`] of an authority,
+    /// users can slash, score peers or alert here.
+    /// It is called in the main loop of the state machine, so it should return quickly.
+    fn report_misbehavior(&self, _misbehavior: Misbehavior) {}
 }
 
 /// A public function for proof validation.
 /// The input [`height`] is the height of block containing the proof.
 /// The input [`authorities`] is the authority_list for the proof check.
 /// The fn [`crypt_hash`], [`check_sig`] are user-defined.
+/// The input [`quorum`] must be the `commit_quorum` of the config of the BFT state machine,
+/// which the proof must exceed.
 pub fn check_proof(
     proof: &Proof,
     height: Height,
     authorities: &[Node],
+    quorum: Quorum,
     crypt_hash: impl Fn(&[u8]) -> Hash,
     check_sig: impl Fn(&Signature, &Hash) -> Option<Address>,
 ) -> bool {
@@ -582,13 +1031,12 @@ pub fn check_proof(
         return false;
     }
 
-    let vote_addresses: Vec<Address> = proof
-        .precommit_votes
-        .iter()
-        .map(|(sender, _)| sender.clone())
-        .collect();
+    let vote_addresses: Vec<Address> = proof.precommit_votes.keys().cloned().collect();
 
-    if get_votes_weight(authorities, &vote_addresses) * 3 <= get_total_weight(authorities) * 2 {
+    if !quorum.is_reached(
+        get_votes_weight(authorities, &vote_addresses),
+        get_total_weight(authorities),
+    ) {
         return false;
     }
 
@@ -610,9 +1058,73 @@ pub fn check_proof(
     })
 }
 
+/// A public function for evidence validation.
+/// It checks that the two signed messages are signed by [`evidence.address`]
+/// with the same height, round and type, but for different contents.
+/// The fn [`crypt_hash`], [`check_sig`] are user-defined.
+pub fn check_evidence(
+    evidence: &Evidence,
+    crypt_hash: impl Fn(&[u8]) -> Hash,
+    check_sig: impl Fn(&Signature, &Hash) -> Option<Address>,
+) -> bool {
+    let vote_type = match evidence.evidence_type {
+        EvidenceType::DuplicatePrevote => VoteType::Prevote,
+        EvidenceType::DuplicatePrecommit => VoteType::Precommit,
+        EvidenceType::DuplicateProposal => {
+            return check_proposal_evidence(evidence, crypt_hash, check_sig);
+        }
+    };
+    let (first, second): (SignedVote, SignedVote) =
+        match (rlp::decode(&evidence.first), rlp::decode(&evidence.second)) {
+            (Ok(first), Ok(second)) => (first, second),
+            _ => return false,
+        };
+    if first.vote.block_hash == second.vote.block_hash {
+        return false;
+    }
+    [first, second].iter().all(|signed_vote| {
+        let vote = &signed_vote.vote;
+        if vote.vote_type != vote_type
+            || vote.height != evidence.height
+            || vote.round != evidence.round
+            || vote.voter != evidence.address
+        {
+            return false;
+        }
+        let hash = crypt_hash(&rlp::encode(vote));
+        check_sig(&signed_vote.signature, &hash) == Some(evidence.address.clone())
+    })
+}
+
+fn check_proposal_evidence(
+    evidence: &Evidence,
+    crypt_hash: impl Fn(&[u8]) -> Hash,
+    check_sig: impl Fn(&Signature, &Hash) -> Option<Address>,
+) -> bool {
+    let (first, second): (SignedProposal, SignedProposal) =
+        match (rlp::decode(&evidence.first), rlp::decode(&evidence.second)) {
+            (Ok(first), Ok(second)) => (first, second),
+            _ => return false,
+        };
+    if first.proposal == second.proposal {
+        return false;
+    }
+    [first, second].iter().all(|signed_proposal| {
+        let proposal = &signed_proposal.proposal;
+        if proposal.height != evidence.height
+            || proposal.round != evidence.round
+            || proposal.proposer != evidence.address
+        {
+            return false;
+        }
+        let hash = crypt_hash(&rlp::encode(proposal));
+        check_sig(&signed_proposal.signature, &hash) == Some(evidence.address.clone())
+    })
+}
+
 /// A public function for get_proposal_hash from BftMsg::Proposal
 pub fn get_proposal_hash(encode: &[u8], crypt_hash: impl Fn(&[u8]) -> Hash) -> Option<Hash> {
-    if let Ok((signed_proposal_encode, _)) = extract_two(&encode) {
+    if let Ok((signed_proposal_encode, _)) = extract_two(encode) {
         Some(crypt_hash(signed_proposal_encode))
     } else {
         None
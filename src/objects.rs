@@ -241,13 +241,14 @@ impl AuthorityManage {
     }
 }
 
-#[derive(Debug, PartialEq, PartialOrd, Eq, Clone, Copy, Hash)]
-pub(crate) enum Step {
+/// The step of a round of the BFT state machine.
+#[derive(Debug, PartialEq, PartialOrd, Eq, Clone, Copy, Hash, Default)]
+pub enum Step {
+    #[default]
     Propose,
     ProposeWait,
     Prevote,
     PrevoteWait,
-    #[cfg(feature = "verify_req")]
     VerifyWait,
     Precommit,
     PrecommitWait,
@@ -255,12 +256,6 @@ pub(crate) enum Step {
     CommitWait,
 }
 
-impl Default for Step {
-    fn default() -> Step {
-        Step::Propose
-    }
-}
-
 impl From<u8> for Step {
     fn from(s: u8) -> Self {
         match s {
@@ -268,7 +263,6 @@ impl From<u8> for Step {
             1 => Step::ProposeWait,
             2 => Step::Prevote,
             3 => Step::PrevoteWait,
-            #[cfg(feature = "verify_req")]
             4 => Step::VerifyWait,
             5 => Step::Precommit,
             6 => Step::PrecommitWait,
@@ -279,14 +273,13 @@ impl From<u8> for Step {
     }
 }
 
-impl Into<u8> for Step {
-    fn into(self) -> u8 {
-        match self {
+impl From<Step> for u8 {
+    fn from(s: Step) -> Self {
+        match s {
             Step::Propose => 0,
             Step::ProposeWait => 1,
             Step::Prevote => 2,
             Step::PrevoteWait => 3,
-            #[cfg(feature = "verify_req")]
             Step::VerifyWait => 4,
             Step::Precommit => 5,
             Step::PrecommitWait => 6,
@@ -312,9 +305,9 @@ impl From<u8> for VoteType {
     }
 }
 
-impl Into<u8> for VoteType {
-    fn into(self) -> u8 {
-        match self {
+impl From<VoteType> for u8 {
+    fn from(s: VoteType) -> Self {
+        match s {
             VoteType::Prevote => 0,
             VoteType::Precommit => 1,
         }
@@ -331,6 +324,10 @@ pub(crate) enum LogType {
     VerifyResp,
     TimeOutInfo,
     Block,
+    Evidence,
+    ProposalBlock,
+    FailedProposers,
+    ElectionState,
 }
 
 impl From<u8> for LogType {
@@ -344,14 +341,18 @@ impl From<u8> for LogType {
             5 => LogType::VerifyResp,
             6 => LogType::TimeOutInfo,
             7 => LogType::Block,
+            8 => LogType::Evidence,
+            9 => LogType::ProposalBlock,
+            10 => LogType::FailedProposers,
+            11 => LogType::ElectionState,
             _ => panic!("Invalid vote type!"),
         }
     }
 }
 
-impl Into<u8> for LogType {
-    fn into(self) -> u8 {
-        match self {
+impl From<LogType> for u8 {
+    fn from(s: LogType) -> Self {
+        match s {
             LogType::Proposal => 0,
             LogType::Vote => 1,
             LogType::Status => 2,
@@ -360,6 +361,10 @@ impl Into<u8> for LogType {
             LogType::VerifyResp => 5,
             LogType::TimeOutInfo => 6,
             LogType::Block => 7,
+            LogType::Evidence => 8,
+            LogType::ProposalBlock => 9,
+            LogType::FailedProposers => 10,
+            LogType::ElectionState => 11,
         }
     }
 }
@@ -1,8 +1,317 @@
-use crate::Address;
+use crate::clock::Clock;
+use crate::election::ProposerElection;
+use crate::error::{BftError, BftResult};
+use crate::{Address, Round, WalConfig};
 
 use std::cell::Cell;
+use std::sync::Arc;
 use std::time::Duration;
 
+const DEFAULT_TOTAL_DURATION: u64 = 3000;
+const MAX_BACKOFF_LIMIT: u64 = 16;
+// the max wait duration, whose nanos fit in the u64 duration of a timeout info
+const MAX_WAIT_DURATION: Duration = Duration::from_secs(u32::MAX as u64);
+
+/// The fraction of the total vote weight which must be exceeded to reach a quorum.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Quorum {
+    pub(crate) numerator: u64,
+    pub(crate) denominator: u64,
+}
+
+impl Quorum {
+    /// More than 2/3 of the total weight, the classic rule of `n >= 3f + 1`.
+    pub const TWO_THIRDS: Quorum = Quorum {
+        numerator: 2,
+        denominator: 3,
+    };
+
+    /// A function to create a quorum of more than `numerator / denominator` of the total weight,
+    /// which must be in [2/3, 1) to be safe.
+    pub fn new(numerator: u64, denominator: u64) -> BftResult<Self> {
+        // a quorum below 2/3 is unsafe, and a quorum of 1 can never be exceeded
+        if numerator >= denominator || u128::from(numerator) * 3 < u128::from(denominator) * 2 {
+            return Err(BftError::InvalidConfig(format!(
+                "quorum {}/{} is not in [2/3, 1)",
+                numerator, denominator
+            )));
+        }
+        Ok(Quorum {
+            numerator,
+            denominator,
+        })
+    }
+
+    /// A function to check whether the weight reaches the quorum of the total weight.
+    pub fn is_reached(&self, weight: u64, total_weight: u64) -> bool {
+        u128::from(weight) * u128::from(self.denominator)
+            > u128::from(total_weight) * u128::from(self.numerator)
+    }
+}
+
+impl Default for Quorum {
+    fn default() -> Self {
+        Quorum::TWO_THIRDS
+    }
+}
+
+/// The growth of the propose, prevote and precommit waits in the later rounds of a height,
+/// the waits are reset at a new height.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum RoundBackoff {
+    /// The wait of round r is `(r + 1)` times the one of round 0,
+    /// like the `timeout_*_delta` of Tendermint.
+    Linear,
+    /// The wait of round r is `2^r` times the one of round 0.
+    Exponential,
+}
+
+/// The runtime config of the BFT state machine, which is built by `BftConfig::builder`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct BftConfig {
+    pub(crate) propose: (u64, u64),
+    pub(crate) prevote: (u64, u64),
+    pub(crate) precommit: (u64, u64),
+    pub(crate) round_backoff: RoundBackoff,
+    pub(crate) backoff_limit: u64,
+    pub(crate) reputation_window: u64,
+    pub(crate) commit_quorum: Quorum,
+    pub(crate) lock_quorum: Quorum,
+    pub(crate) retransmit_coef: u32,
+    pub(crate) verify_await_coef: u32,
+    pub(crate) cache_size: u64,
+    pub(crate) low_height_message_coef: u32,
+    pub(crate) verify_req: bool,
+    pub(crate) machine_gun: bool,
+    pub(crate) random_proposer: bool,
+    pub(crate) compact_block: bool,
+    pub(crate) wal: WalConfig,
+}
+
+impl Default for BftConfig {
+    fn default() -> Self {
+        BftConfig {
+            propose: (24, 30),
+            prevote: (1, 30),
+            precommit: (1, 30),
+            round_backoff: RoundBackoff::Exponential,
+            backoff_limit: 4,
+            reputation_window: 0,
+            commit_quorum: Quorum::TWO_THIRDS,
+            lock_quorum: Quorum::TWO_THIRDS,
+            retransmit_coef: 15,
+            verify_await_coef: 50,
+            cache_size: 16,
+            low_height_message_coef: 20,
+            verify_req: cfg!(feature = "verify_req"),
+            machine_gun: cfg!(feature = "machine_gun"),
+            random_proposer: cfg!(feature = "random_proposer"),
+            compact_block: cfg!(feature = "compact_block"),
+            wal: WalConfig::default(),
+        }
+    }
+}
+
+impl BftConfig {
+    /// A function to create a config builder with the default values.
+    pub fn builder() -> BftConfigBuilder {
+        BftConfigBuilder(BftConfig::default())
+    }
+}
+
+/// A builder of `BftConfig`, the values are validated by `build`.
+#[derive(Debug, Clone, Copy)]
+pub struct BftConfigBuilder(BftConfig);
+
+impl BftConfigBuilder {
+    /// A function to set the fraction of the interval to wait for a proposal, 24/30 by default.
+    pub fn propose_fraction(mut self, numerator: u64, denominator: u64) -> Self {
+        self.0.propose = (numerator, denominator);
+        self
+    }
+
+    /// A function to set the fraction of the interval to wait for more prevotes, 1/30 by default.
+    pub fn prevote_fraction(mut self, numerator: u64, denominator: u64) -> Self {
+        self.0.prevote = (numerator, denominator);
+        self
+    }
+
+    /// A function to set the fraction of the interval to wait for more precommits, 1/30 by default.
+    pub fn precommit_fraction(mut self, numerator: u64, denominator: u64) -> Self {
+        self.0.precommit = (numerator, denominator);
+        self
+    }
+
+    /// A function to set how the propose, prevote and precommit waits grow
+    /// in later rounds, `RoundBackoff::Exponential` by default.
+    pub fn round_backoff(mut self, backoff: RoundBackoff) -> Self {
+        self.0.round_backoff = backoff;
+        self
+    }
+
+    /// A function to set the round after which the waits stop growing, 4 by default.
+    /// The waits do not grow if it is 0.
+    pub fn backoff_limit(mut self, limit: u64) -> Self {
+        self.0.backoff_limit = limit;
+        self
+    }
+
+    /// A function to set how many heights a proposer is skipped in the election
+    /// after its round failed to commit, 0 (never) by default.
+    /// The failed rounds are derived from the proofs, so a node that missed the proofs of
+    /// some heights, e.g. after a sync, may skip other proposers until the window passes.
+    pub fn reputation_window(mut self, heights: u64) -> Self {
+        self.0.reputation_window = heights;
+        self
+    }
+
+    /// A function to set the quorum of precommits to commit a block, which also checks
+    /// the proofs, `Quorum::TWO_THIRDS` by default.
+    pub fn commit_quorum(mut self, quorum: Quorum) -> Self {
+        self.0.commit_quorum = quorum;
+        self
+    }
+
+    /// A function to set the quorum of prevotes to lock a block, which also checks
+    /// the lock votes of the proposals, `Quorum::TWO_THIRDS` by default.
+    pub fn lock_quorum(mut self, quorum: Quorum) -> Self {
+        self.0.lock_quorum = quorum;
+        self
+    }
+
+    /// A function to set the multiple of the prevote (or precommit) wait
+    /// to retransmit the votes, 15 by default.
+    pub fn retransmit_coef(mut self, coef: u32) -> Self {
+        self.0.retransmit_coef = coef;
+        self
+    }
+
+    /// A function to set the multiple of the prevote wait to wait for a block verification
+    /// with the feature `verify_req`, 50 by default.
+    pub fn verify_await_coef(mut self, coef: u32) -> Self {
+        self.0.verify_await_coef = coef;
+        self
+    }
+
+    /// A function to set how many heights and rounds of msgs are cached, 16 by default.
+    pub fn cache_size(mut self, size: u64) -> Self {
+        self.0.cache_size = size;
+        self
+    }
+
+    /// A function to set the multiple of the prevote wait before the votes are retransmitted
+    /// again to a node falling behind, 20 by default.
+    pub fn low_height_message_coef(mut self, coef: u32) -> Self {
+        self.0.low_height_message_coef = coef;
+        self
+    }
+
+    /// A function to set whether the blocks are verified asynchronously,
+    /// the result is fed back as `BftMsg::VerifyResp`. It defaults to the feature `verify_req`.
+    pub fn verify_req(mut self, enable: bool) -> Self {
+        self.0.verify_req = enable;
+        self
+    }
+
+    /// A function to set whether a new height starts as soon as the status is received,
+    /// without waiting for the interval. It defaults to the feature `machine_gun`.
+    pub fn machine_gun(mut self, enable: bool) -> Self {
+        self.0.machine_gun = enable;
+        self
+    }
+
+    /// A function to set whether the proposer is chosen randomly by the weights,
+    /// instead of in turn, if no proposer election is given.
+    /// It defaults to the feature `random_proposer`.
+    pub fn random_proposer(mut self, enable: bool) -> Self {
+        self.0.random_proposer = enable;
+        self
+    }
+
+    /// A function to set whether the committed block is the complete block
+    /// of `VerifyResp`, instead of the proposed one. It defaults to the feature `compact_block`.
+    pub fn compact_block(mut self, enable: bool) -> Self {
+        self.0.compact_block = enable;
+        self
+    }
+
+    /// A function to set the wal config.
+    pub fn wal_config(mut self, wal: WalConfig) -> Self {
+        self.0.wal = wal;
+        self
+    }
+
+    /// A function to validate the values and build the config.
+    pub fn build(self) -> BftResult<BftConfig> {
+        let config = self.0;
+        for (name, (numerator, denominator)) in [
+            ("propose", config.propose),
+            ("prevote", config.prevote),
+            ("precommit", config.precommit),
+        ] {
+            if numerator == 0 || denominator == 0 || numerator > denominator {
+                return Err(BftError::InvalidConfig(format!(
+                    "{} fraction {}/{} is not in (0, 1]",
+                    name, numerator, denominator
+                )));
+            }
+        }
+        let (propose, prevote, precommit) = (config.propose, config.prevote, config.precommit);
+        // compare the sum of the fractions with 1 without rounding
+        let product = |a: u64, b: u64, c: u64| {
+            u128::from(a)
+                .checked_mul(u128::from(b))
+                .and_then(|ab| ab.checked_mul(u128::from(c)))
+        };
+        let sum = product(propose.0, prevote.1, precommit.1)
+            .zip(product(prevote.0, propose.1, precommit.1))
+            .zip(product(precommit.0, propose.1, prevote.1))
+            .and_then(|((a, b), c)| a.checked_add(b)?.checked_add(c));
+        let one = product(propose.1, prevote.1, precommit.1);
+        match sum.zip(one) {
+            Some((sum, one)) if sum > one => {
+                return Err(BftError::InvalidConfig(
+                    "the sum of the fractions is larger than 1".to_string(),
+                ));
+            }
+            None => {
+                return Err(BftError::InvalidConfig(
+                    "the denominators of the fractions are too large".to_string(),
+                ));
+            }
+            _ => {}
+        }
+        if config.backoff_limit > MAX_BACKOFF_LIMIT {
+            return Err(BftError::InvalidConfig(format!(
+                "backoff limit {} is larger than {}",
+                config.backoff_limit, MAX_BACKOFF_LIMIT
+            )));
+        }
+        if config.retransmit_coef == 0
+            || config.verify_await_coef == 0
+            || config.low_height_message_coef == 0
+        {
+            return Err(BftError::InvalidConfig(
+                "the coefficients must be positive".to_string(),
+            ));
+        }
+        // the votes of the last height may be retransmitted, which are saved in its wal log
+        if config.wal.retention == 0 {
+            return Err(BftError::InvalidConfig(
+                "the wal retention must be positive".to_string(),
+            ));
+        }
+        // the msgs of the next height must be cached
+        if config.cache_size < 2 {
+            return Err(BftError::InvalidConfig(format!(
+                "cache size {} is less than 2",
+                config.cache_size
+            )));
+        }
+        Ok(config)
+    }
+}
+
 /// BFT params.
 #[derive(Clone, Debug)]
 pub(crate) struct BftParams {
@@ -10,14 +319,28 @@ pub(crate) struct BftParams {
     pub(crate) address: Address,
     /// A set of BFT timer settings.
     pub(crate) timer: BftTimer,
+    /// The source of time.
+    pub(crate) clock: Arc<dyn Clock>,
+    /// The runtime config.
+    pub(crate) config: BftConfig,
+    /// The proposer election.
+    pub(crate) election: Arc<dyn ProposerElection>,
 }
 
 impl BftParams {
     /// A function to create a new BFT params.
-    pub(crate) fn new(local_address: Address) -> Self {
+    pub(crate) fn new(
+        local_address: Address,
+        config: BftConfig,
+        clock: Arc<dyn Clock>,
+        election: Arc<dyn ProposerElection>,
+    ) -> Self {
         BftParams {
             address: local_address,
-            timer: BftTimer::default(),
+            timer: BftTimer::new(&config),
+            clock,
+            config,
+            election,
         }
     }
 }
@@ -31,41 +354,82 @@ pub(crate) struct BftTimer {
     propose: (u64, u64),
     prevote: (u64, u64),
     precommit: (u64, u64),
+    round_backoff: RoundBackoff,
+    backoff_limit: u64,
 }
 
-impl Default for BftTimer {
-    fn default() -> Self {
+impl BftTimer {
+    /// A function to create a BFT timer with the fractions of the config.
+    pub(crate) fn new(config: &BftConfig) -> Self {
         BftTimer {
-            total_duration: Cell::new(3000),
-            propose: (24, 30),
-            prevote: (1, 30),
-            precommit: (1, 30),
+            total_duration: Cell::new(DEFAULT_TOTAL_DURATION),
+            propose: config.propose,
+            prevote: config.prevote,
+            precommit: config.precommit,
+            round_backoff: config.round_backoff,
+            backoff_limit: config.backoff_limit,
         }
     }
-}
 
-impl BftTimer {
     /// A function to set total interval.
     pub(crate) fn set_total_duration(&self, duration: u64) {
         self.total_duration.set(duration);
     }
 
+    /// A function to get the fraction of the total interval, which never overflows
+    /// as the fraction is not larger than 1.
+    fn get_fraction(&self, (numerator, denominator): (u64, u64)) -> Duration {
+        let millis =
+            u128::from(self.total_duration.get()) * u128::from(numerator) / u128::from(denominator);
+        Duration::from_millis(millis as u64)
+    }
+
     /// A function to get propose wait duration.
     pub(crate) fn get_propose(&self) -> Duration {
-        Duration::from_millis(self.total_duration.get() * self.propose.0 / self.propose.1)
+        self.get_fraction(self.propose)
     }
 
     /// A function to get prevote wait duration.
     pub(crate) fn get_prevote(&self) -> Duration {
-        Duration::from_millis(self.total_duration.get() * self.prevote.0 / self.prevote.1)
+        self.get_fraction(self.prevote)
     }
 
     /// A function to get precommit wait duration.
     pub(crate) fn get_precommit(&self) -> Duration {
-        Duration::from_millis(self.total_duration.get() * self.precommit.0 / self.precommit.1)
+        self.get_fraction(self.precommit)
+    }
+
+    /// A function to get the propose wait duration of a round.
+    pub(crate) fn get_propose_wait(&self, round: Round) -> Duration {
+        self.get_backoff_wait(self.get_propose(), round)
+    }
+
+    /// A function to get the prevote wait duration of a round.
+    pub(crate) fn get_prevote_wait(&self, round: Round) -> Duration {
+        self.get_backoff_wait(self.get_prevote(), round)
+    }
+
+    /// A function to get the precommit wait duration of a round.
+    pub(crate) fn get_precommit_wait(&self, round: Round) -> Duration {
+        self.get_backoff_wait(self.get_precommit(), round)
+    }
+
+    /// A function to get the wait duration backed off by the round,
+    /// which saturates to the max wait duration instead of overflowing.
+    fn get_backoff_wait(&self, duration: Duration, round: Round) -> Duration {
+        duration
+            .checked_mul(self.get_backoff_coef(round))
+            .map_or(MAX_WAIT_DURATION, |wait| wait.min(MAX_WAIT_DURATION))
+    }
+
+    fn get_backoff_coef(&self, round: Round) -> u32 {
+        let round = round.min(self.backoff_limit) as u32;
+        match self.round_backoff {
+            RoundBackoff::Linear => round + 1,
+            RoundBackoff::Exponential => 2u32.pow(round),
+        }
     }
 
-    #[cfg(not(feature = "machine_gun"))]
     pub(crate) fn get_total_duration(&self) -> Duration {
         Duration::from_millis(self.total_duration.get())
     }
@@ -1,21 +1,33 @@
+use crate::clock::{system_clock, Clock};
 use crate::objects::Step;
 use crate::{Height, Round};
 
 use std::cmp::{Ord, Ordering, PartialOrd};
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 
-use crossbeam::crossbeam_channel::{Receiver, Sender};
+use crossbeam::crossbeam_channel::{Receiver, RecvTimeoutError, Sender};
 #[allow(unused_imports)]
 use log::{log, warn};
 use min_max_heap::MinMaxHeap;
-use rlp::{Decodable, DecoderError, Encodable, Prototype, Rlp, RlpStream};
+use rlp::{DecoderError, Encodable, Prototype, Rlp, RlpStream};
+
+/// The kind of a timer.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub(crate) enum TimerKind {
+    /// The timer of the step of the round.
+    Step,
+    /// The timer to sync the wal records of a group commit,
+    /// which is not bound to the height, round or step.
+    WalSync,
+}
 
 /// Timer infomation.
 #[derive(Debug, Clone, Eq, PartialEq)]
-pub(crate) struct TimeoutInfo {
+pub struct TimeoutInfo {
     /// A timestamp of a timer.
     pub(crate) timestamp: Instant,
-    ///
+    /// The duration since the height start.
     pub(crate) duration: u64,
     /// The height of the timer.
     pub(crate) height: Height,
@@ -23,11 +35,13 @@ pub(crate) struct TimeoutInfo {
     pub(crate) round: Round,
     /// The step of the timer.
     pub(crate) step: Step,
+    /// The kind of the timer, only the timers of the steps are saved in the wal log.
+    pub(crate) kind: TimerKind,
 }
 
 impl PartialOrd for TimeoutInfo {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        self.timestamp.partial_cmp(&other.timestamp)
+        Some(self.cmp(other))
     }
 }
 
@@ -48,8 +62,10 @@ impl Encodable for TimeoutInfo {
     }
 }
 
-impl Decodable for TimeoutInfo {
-    fn decode(r: &Rlp) -> Result<Self, DecoderError> {
+impl TimeoutInfo {
+    /// A function to decode a timeout info, whose timestamp is recovered from the current time.
+    pub(crate) fn decode(encode: &[u8], now: Instant) -> Result<Self, DecoderError> {
+        let r = Rlp::new(encode);
         match r.prototype()? {
             Prototype::List(4) => {
                 let duration: u64 = r.val_at(0)?;
@@ -58,11 +74,12 @@ impl Decodable for TimeoutInfo {
                 let step: u8 = r.val_at(3)?;
                 let step: Step = Step::from(step);
                 Ok(TimeoutInfo {
-                    timestamp: Instant::now() + Duration::from_nanos(duration),
+                    timestamp: now + Duration::from_nanos(duration),
                     duration,
                     height,
                     round,
                     step,
+                    kind: TimerKind::Step,
                 })
             }
             _ => Err(DecoderError::RlpInconsistentLengthAndData),
@@ -87,6 +104,7 @@ where
 {
     timer_seter: Receiver<T>,
     timer_notify: Sender<T>,
+    clock: Arc<dyn Clock>,
 }
 
 impl<T> WaitTimer<T>
@@ -95,9 +113,15 @@ where
 {
     /// A function to create a new timeout infomation channel.
     pub fn new(ts: Sender<T>, rs: Receiver<T>) -> WaitTimer<T> {
+        WaitTimer::with_clock(ts, rs, system_clock())
+    }
+
+    /// A function to create a new timeout infomation channel following the clock.
+    pub fn with_clock(ts: Sender<T>, rs: Receiver<T>, clock: Arc<dyn Clock>) -> WaitTimer<T> {
         WaitTimer {
             timer_notify: ts,
             timer_seter: rs,
+            clock,
         }
     }
 
@@ -109,12 +133,7 @@ where
             // take the peek of the min-heap-timer sub now as the sleep time otherwise set timeout as 100
             let timeout = if !timer_heap.is_empty() {
                 let peek_min_time = timer_heap.peek_min().unwrap().get_instant();
-                let now = Instant::now();
-                if peek_min_time > now {
-                    peek_min_time - now
-                } else {
-                    Duration::new(0, 0)
-                }
+                self.clock.wait_duration(peek_min_time)
             } else {
                 Duration::from_secs(100)
             };
@@ -123,13 +142,15 @@ where
 
             // put the timeval into a timerheap
             // put the TimeoutInfo into a hashmap, K: timeval  V: TimeoutInfo
-            if set_time.is_ok() {
-                let time_out = set_time.unwrap();
-                timer_heap.push(time_out);
+            match set_time {
+                Ok(time_out) => timer_heap.push(time_out),
+                // the state machine has stopped
+                Err(RecvTimeoutError::Disconnected) => break,
+                Err(RecvTimeoutError::Timeout) => {}
             }
 
             if !timer_heap.is_empty() {
-                let now = Instant::now();
+                let now = self.clock.now();
 
                 // if some timers are set as the same time, send timeout messages and pop them
                 while !timer_heap.is_empty()
@@ -1,47 +1,59 @@
 use crate::*;
 use crate::{
     algorithm::{Bft, INIT_HEIGHT, INIT_ROUND},
-    collectors::{ProposalCollector, RoundCollector, VoteCollector, VoteSet, CACHE_N},
+    collectors::{EvidenceCollector, ProposalCollector, RoundCollector, VoteCollector, VoteSet},
+    engine::{BftAction, CheckBlockReq},
     error::{handle_err, BftError, BftResult},
     objects::*,
-    timer::TimeoutInfo,
-    wal::Wal,
+    params::Quorum,
+    timer::{TimeoutInfo, TimerKind},
 };
-#[cfg(feature = "verify_req")]
 #[allow(unused_imports)]
 use log::{log, warn};
-#[cfg(feature = "random_proposer")]
-use rand_core::{RngCore, SeedableRng};
-#[cfg(feature = "random_proposer")]
-use rand_pcg::Pcg64Mcg as Pcg;
-#[cfg(feature = "verify_req")]
-use std::collections::HashMap;
-use std::fs;
-#[cfg(feature = "verify_req")]
+use std::collections::{BTreeMap, HashMap};
 use std::thread;
-use std::time::{Duration, Instant};
+use std::time::Duration;
 
-const TIMEOUT_LOW_HEIGHT_MESSAGE_COEF: u32 = 20;
 const TIMEOUT_LOW_ROUND_MESSAGE_COEF: u32 = 20;
 
 impl<T> Bft<T>
 where
     T: BftSupport + 'static,
 {
-    pub(crate) fn load_wal_log(&mut self) {
+    pub(crate) fn load_wal_log(&mut self) -> BftResult<()> {
         info!("Node {:?} starts loading wal log!", self.params.address);
-        let vec_buf = self.wal_log.load();
+        let vec_buf = self
+            .wal_log
+            .load()
+            .map_err(|e| BftError::LoadWalErr(format!("{:?}", e)))?;
+        // the node's own votes are known before the replay, so no conflicting one is signed
+        // when a replayed proposal comes before them
+        self.wal_votes = vec_buf
+            .iter()
+            .filter_map(|(log_type, encode)| match log_type {
+                LogType::Vote => rlp::decode::<SignedVote>(encode).ok(),
+                _ => None,
+            })
+            .filter(|signed_vote| signed_vote.vote.voter == self.params.address)
+            .collect();
         for (log_type, encode) in vec_buf {
             handle_err(self.process_wal_log(log_type, encode), &self.params.address);
         }
+        self.wal_votes.clear();
         info!(
             "Node {:?} successfully processed the whole wal log!",
             self.params.address
         );
+        Ok(())
     }
 
     fn process_wal_log(&mut self, log_type: LogType, encode: Vec<u8>) -> BftResult<()> {
         match log_type {
+            LogType::ProposalBlock => {
+                info!("Node {:?} loads proposal with block", self.params.address);
+                self.process(BftMsg::Proposal(encode), false)?;
+            }
+            // only in wal logs written before proposals were saved with their blocks
             LogType::Proposal => {
                 info!("Node {:?} loads proposal", self.params.address);
                 let signed_proposal: SignedProposal = rlp::decode(&encode).map_err(|e| {
@@ -57,7 +69,7 @@ where
                             "can not fetch block from cache when load signed_proposal".to_string(),
                         )
                     })?;
-                let proposal_block_encode = combine_two(&encode, &block);
+                let proposal_block_encode = combine_two(&encode, block);
                 self.process(BftMsg::Proposal(proposal_block_encode), false)?;
             }
             LogType::Vote => {
@@ -82,6 +94,16 @@ where
                     .map_err(|e| BftError::DecodeErr(format!("proof encounters {:?}", e)))?;
                 self.set_proof(&proof);
             }
+            LogType::FailedProposers => {
+                info!("Node {:?} loads failed proposers", self.params.address);
+                let failed_proposers = decode_failed_proposers(&encode)?;
+                self.failed_proposers.extend(failed_proposers);
+            }
+            LogType::ElectionState => {
+                info!("Node {:?} loads election state", self.params.address);
+                let (height, state) = decode_election_state(&encode)?;
+                self.election_states.insert(height, state);
+            }
             LogType::VerifyResp => {
                 info!("Node {:?} loads verify_resp", self.params.address);
                 let verify_resp: VerifyResp = rlp::decode(&encode)
@@ -91,9 +113,10 @@ where
 
             LogType::TimeOutInfo => {
                 info!("Node {:?} loads timeout_info", self.params.address);
-                let time_out_info: TimeoutInfo = rlp::decode(&encode).map_err(|e| {
-                    BftError::DecodeErr(format!("time_out_info encounters {:?}", e))
-                })?;
+                let time_out_info =
+                    TimeoutInfo::decode(&encode, self.params.clock.now()).map_err(|e| {
+                        BftError::DecodeErr(format!("time_out_info encounters {:?}", e))
+                    })?;
                 self.timeout_process(time_out_info, false)?;
             }
 
@@ -102,17 +125,24 @@ where
                 let (height, block, block_hash) = decode_block(&encode)?;
                 self.blocks.add(height, &block_hash, &block);
             }
+
+            LogType::Evidence => {
+                info!("Node {:?} loads evidence", self.params.address);
+                let evidence: Evidence = rlp::decode(&encode)
+                    .map_err(|e| BftError::DecodeErr(format!("evidence encounters {:?}", e)))?;
+                self.evidences.add(&evidence);
+            }
         }
         Ok(())
     }
 
     pub(crate) fn build_signed_proposal_encode(
         &mut self,
-        proposal: &Proposal,
+        signed_proposal: &SignedProposal,
     ) -> BftResult<Vec<u8>> {
+        let proposal = &signed_proposal.proposal;
         let block_hash = &proposal.block_hash;
-        let signed_proposal = self.build_signed_proposal(&proposal)?;
-        let signed_proposal_encode = rlp::encode(&signed_proposal);
+        let signed_proposal_encode = rlp::encode(signed_proposal);
         let block = self
             .blocks
             .get_block(proposal.height, block_hash)
@@ -121,7 +151,7 @@ where
                     "can not fetch block from cache when send signed_proposal".to_string(),
                 )
             })?;
-        let encode = combine_two(&signed_proposal_encode, &block);
+        let encode = combine_two(&signed_proposal_encode, block);
         Ok(encode)
     }
 
@@ -173,7 +203,7 @@ where
     }
 
     #[inline]
-    fn get_vote_weight(&self, height: Height, address: &Address) -> u64 {
+    pub(crate) fn get_vote_weight(&self, height: Height, address: &Address) -> u64 {
         if height != self.height {
             return 1;
         }
@@ -188,35 +218,98 @@ where
         1
     }
 
-    pub(crate) fn get_proposer(&self, height: Height, round: Round) -> BftResult<&Address> {
+    /// A function to get the proof of the previous height of the height, if the node has got it.
+    pub(crate) fn get_previous_proof(&self, height: Height) -> Option<&Proof> {
+        if self.proof.height + 1 == height {
+            Some(&self.proof)
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn get_proposer(
+        &self,
+        height: Height,
+        round: Round,
+        proof: Option<&Proof>,
+    ) -> BftResult<&Address> {
         let authorities = self.get_authorities(height)?;
-        let nonce = height + round;
-        let weight: Vec<u64> = authorities
-            .iter()
-            .map(|node| u64::from(node.proposal_weight))
+        // the election always gets the whole authority list
+        let election = &self.params.election;
+        let index = match self.election_states.get(&height) {
+            Some(state) => election.elect_by_state(height, round, authorities, proof, state),
+            None => election.elect(height, round, authorities, proof),
+        };
+        if index >= authorities.len() {
+            return Err(BftError::ShouldNotHappen(format!(
+                "the elected index {} is out of the authorities at h:{} r:{}",
+                index, height, round
+            )));
+        }
+        // skip the proposers failed in the window to the next one in the authority list,
+        // unless all of them failed
+        let window = self.params.config.reputation_window;
+        let failed: Vec<&Address> = self
+            .failed_proposers
+            .range(height.saturating_sub(window)..height)
+            .flat_map(|(_, addresses)| addresses)
             .collect();
-        let proposer: &Address = &authorities
-            .get(get_index(nonce, &weight))
-            .unwrap_or_else(|| {
-                panic!(
-                    "Node {:?} selects a proposer not in authorities, it should not happen!",
-                    self.params.address
-                )
-            })
-            .address;
-        Ok(proposer)
+        let proposer = (0..authorities.len())
+            .map(|offset| &authorities[(index + offset) % authorities.len()])
+            .find(|node| !failed.contains(&&node.address))
+            .unwrap_or(&authorities[index]);
+        Ok(&proposer.address)
     }
 
     #[inline]
     pub(crate) fn set_proof(&mut self, proof: &Proof) {
         if self.proof.height < proof.height {
+            self.record_failed_proposers(proof);
             self.proof = proof.clone();
         }
     }
 
+    /// The rounds before the reaching-consensus round of the proof failed to commit, their
+    /// proposers are derived from the signed round of the proof and the previous proof.
+    /// Without the previous proof, e.g. after a sync, the proposers are unknown and not recorded.
+    fn record_failed_proposers(&mut self, proof: &Proof) {
+        let window = self.params.config.reputation_window;
+        let height = proof.height;
+        let previous_proof = match self.get_previous_proof(height) {
+            Some(previous_proof) if window > 0 => previous_proof,
+            _ => return,
+        };
+        let mut failed: Vec<Address> = Vec::new();
+        for round in 0..proof.round {
+            if let Ok(proposer) = self.get_proposer(height, round, Some(previous_proof)) {
+                if !failed.contains(proposer) {
+                    failed.push(proposer.clone());
+                }
+            }
+        }
+
+        self.failed_proposers
+            .retain(|failed_height, _| failed_height + window > height);
+        if !failed.is_empty() {
+            self.failed_proposers.insert(height, failed);
+        }
+    }
+
     pub(crate) fn set_status(&mut self, status: &Status) {
         self.authority_manage
             .receive_authorities_list(status.height, status.authority_list.clone());
+        // the state of the election is folded over the authority lists of the heights
+        let height = status.height + 1;
+        if !self.election_states.contains_key(&height) {
+            let state = self.params.election.get_state(
+                height,
+                &self.authority_manage.authorities,
+                self.election_states.get(&status.height).map(Vec::as_slice),
+            );
+            self.election_states.insert(height, state);
+            self.election_states
+                .retain(|state_height, _| state_height + 1 >= height);
+        }
         trace!(
             "Node {:?} updates authority_manage {:?}",
             self.params.address,
@@ -261,6 +354,12 @@ where
             votes: voteset.extract_polc(hash),
         });
 
+        self.emit(BftEvent::LockSet {
+            height: self.height,
+            round: self.round,
+            block_hash: hash.to_owned(),
+        });
+
         debug!(
             "Node {:?} sets a PoLC on block_hash {:?} at h:{:?} r:{:?} ",
             self.params.address,
@@ -270,23 +369,54 @@ where
         );
     }
 
+    pub(crate) fn consensus_state(&self) -> ConsensusState {
+        let mut vote_weights: BTreeMap<Round, VoteWeight> = BTreeMap::new();
+        for (round, weight) in self.votes.prevote_count.iter() {
+            vote_weights.entry(*round).or_default().prevote = *weight;
+        }
+        for (round, weight) in self.votes.precommit_count.iter() {
+            vote_weights.entry(*round).or_default().precommit = *weight;
+        }
+        ConsensusState {
+            height: self.height,
+            round: self.round,
+            step: self.step,
+            lock_round: self.lock_status.as_ref().map(|lock| lock.round),
+            lock_hash: self
+                .lock_status
+                .as_ref()
+                .map(|lock| lock.block_hash.clone()),
+            proof_height: self.proof.height,
+            authority_list: self.authority_manage.authorities.clone(),
+            vote_weights,
+        }
+    }
+
     #[inline]
     pub(crate) fn set_timer(&self, duration: Duration, step: Step) {
         debug!(
             "Node {:?} will process {:?} after {:?}",
             self.params.address, step, duration
         );
-        let timestamp = Instant::now() + duration;
+        self.start_timer(duration, step, TimerKind::Step);
+    }
+
+    fn start_timer(&self, duration: Duration, step: Step, kind: TimerKind) {
+        let timestamp = self.params.clock.now() + duration;
         let since = timestamp - self.htime;
-        self.timer_seter
-            .send(TimeoutInfo {
-                timestamp,
-                duration: since.as_nanos() as u64,
-                height: self.height,
-                round: self.round,
-                step,
-            })
-            .unwrap();
+        let tminfo = TimeoutInfo {
+            timestamp,
+            duration: since.as_nanos() as u64,
+            height: self.height,
+            round: self.round,
+            step,
+            kind,
+        };
+        if self.action_sender.is_some() {
+            self.send_action(BftAction::SetTimer(tminfo));
+        } else {
+            self.timer_seter.send(tminfo).unwrap();
+        }
     }
 
     pub(crate) fn generate_proof(&mut self, lock_status: LockStatus) -> Proof {
@@ -322,7 +452,7 @@ where
                 )
             })?;
             let proposal_encode = rlp::encode(&signed_proposal);
-            let encode = combine_two(&proposal_encode, &block);
+            let encode = combine_two(&proposal_encode, block);
             let msg = BftMsg::Proposal(encode);
             let info = format!("{:?}", &msg);
             self.msg_sender
@@ -335,10 +465,10 @@ where
     pub(crate) fn fetch_votes(&mut self, height: Height) -> BftResult<()> {
         let votes = &mut self.votes.votes;
         let round_votes = votes.get_mut(&height);
-        let mut vote_collector = RoundCollector::new();
+        let mut vote_collector = RoundCollector::new(self.params.config.cache_size);
 
-        if round_votes.is_some() {
-            vote_collector = round_votes.unwrap().clone();
+        if let Some(round_votes) = round_votes {
+            vote_collector = round_votes.clone();
             self.votes.remove(height);
         }
 
@@ -388,30 +518,57 @@ where
         let block_hash = &proposal.block_hash;
         let height = proposal.height;
         let round = proposal.round;
+        let cache_size = self.params.config.cache_size;
 
-        if height < self.height - 1 {
+        if height + 1 < self.height {
             return Err(BftError::ObsoleteMsg(format!("{:?}", signed_proposal)));
         }
 
-        let address = self
-            .function
-            .check_sig(
-                &signed_proposal.signature,
-                &self.function.crypt_hash(&rlp::encode(proposal)),
-            )
-            .map_err(|e| BftError::CheckSigFailed(format!("{:?} of {:?}", e, signed_proposal)))?;
-        if proposal.proposer != address {
-            return Err(BftError::InvalidSender(format!(
-                "recovers {:?} of {:?}",
-                address, signed_proposal
-            )));
+        self.check_signer(
+            height,
+            &proposal.proposer,
+            &signed_proposal.signature,
+            &self.function.crypt_hash(&rlp::encode(proposal)),
+            signed_proposal,
+        )?;
+
+        // a signed conflicting proposal is an evidence, whether its block is valid or not
+        if height >= self.height
+            && height < self.height + cache_size
+            && round < self.round + cache_size
+        {
+            let result = self.proposals.check_equivocation(signed_proposal);
+            self.check_and_save_evidence(&result, need_wal);
+            if let Err(e) = result {
+                if height == self.height {
+                    handle_err(
+                        self.check_conflicting_proposal(
+                            signed_proposal,
+                            block,
+                            signed_proposal_hash,
+                            need_wal,
+                        ),
+                        &self.params.address,
+                    );
+                }
+                return Err(e);
+            }
         }
 
-        if height == self.height || height == self.height - 1 {
+        if height == self.height || height + 1 == self.height {
             self.check_proposer(proposal)?;
-            self.check_lock_votes(proposal, block_hash)?;
+            if let Err(e) = self.check_lock_votes(proposal, block_hash) {
+                if let BftError::CheckLockVotesFailed(reason) = &e {
+                    self.report_misbehavior(Misbehavior::InvalidLockVotes {
+                        address: proposal.proposer.clone(),
+                        signed_proposal: rlp::encode(signed_proposal),
+                        reason: reason.clone(),
+                    });
+                }
+                return Err(e);
+            }
 
-            if height == self.height - 1 {
+            if height + 1 == self.height {
                 return Ok(());
             }
             self.check_block_txs(
@@ -423,40 +580,20 @@ where
         }
 
         // prevent too many higher proposals flush out current proposal
-        if height >= self.height && height < self.height + CACHE_N && round < self.round + CACHE_N {
-            self.proposals.add(&signed_proposal)?;
-            let save = self.blocks.add(height, block_hash, block);
+        if height >= self.height
+            && height < self.height + cache_size
+            && round < self.round + cache_size
+        {
+            self.proposals.add(signed_proposal)?;
+            self.blocks.add(height, block_hash, block);
 
-            if need_wal {
-                if save {
-                    let encode = encode_block(height, block, block_hash);
-                    handle_err(
-                        self.wal_log
-                            .save(height, LogType::Block, &encode)
-                            .or_else(|e| {
-                                Err(BftError::SaveWalErr(format!(
-                                    "{:?} of proposal block with height {}, round {}",
-                                    e, height, round
-                                )))
-                            }),
-                        &self.params.address,
-                    );
-                }
-                handle_err(
-                    self.wal_log
-                        .save(height, LogType::Proposal, &rlp::encode(signed_proposal))
-                        .or_else(|e| {
-                            Err(BftError::SaveWalErr(format!(
-                                "{:?} of {:?}",
-                                e, signed_proposal
-                            )))
-                        }),
-                    &self.params.address,
-                );
+            // the node's own proposal has been saved before it was broadcast
+            if need_wal && proposal.proposer != self.params.address {
+                self.save_proposal_block(signed_proposal, block);
             }
         }
 
-        if height > self.height || (height == self.height && round >= self.round + CACHE_N) {
+        if height > self.height || (height == self.height && round >= self.round + cache_size) {
             return Err(BftError::HigherMsg(format!("{:?}", signed_proposal)));
         }
 
@@ -471,53 +608,83 @@ where
         let vote = &signed_vote.vote;
         let height = vote.height;
         let round = vote.round;
-        if height < self.height - 1 {
+        let cache_size = self.params.config.cache_size;
+        if height + 1 < self.height {
             return Err(BftError::ObsoleteMsg(format!("{:?}", signed_vote)));
         }
 
         let vote_hash = self.function.crypt_hash(&rlp::encode(vote));
-        let address = self
-            .function
-            .check_sig(&signed_vote.signature, &vote_hash)
-            .map_err(|e| BftError::CheckSigFailed(format!("{:?} of {:?}", e, signed_vote)))?;
-        if vote.voter != address {
-            return Err(BftError::InvalidSender(format!(
-                "recovers {:?} of {:?}",
-                address, signed_vote
-            )));
-        }
+        self.check_signer(
+            height,
+            &vote.voter,
+            &signed_vote.signature,
+            &vote_hash,
+            signed_vote,
+        )?;
 
         if height == self.height {
             self.check_voter(vote)?;
         }
 
         // prevent too many high proposals flush out current proposal
-        if height >= self.height && height < self.height + CACHE_N && round < self.round + CACHE_N {
+        if height >= self.height
+            && height < self.height + cache_size
+            && round < self.round + cache_size
+        {
             let vote_weight = self.get_vote_weight(vote.height, &vote.voter);
-            let result = self.votes.add(&signed_vote, vote_weight, self.height);
-            if need_wal && result.is_ok() {
+            let result = self.votes.add(signed_vote, vote_weight, self.height);
+            self.check_and_save_evidence(&result, need_wal);
+            if result.is_ok() && height == self.height {
+                self.check_quorum(vote, vote_weight);
+            }
+            // the node's own vote has been saved before it was broadcast
+            if need_wal && result.is_ok() && vote.voter != self.params.address {
                 handle_err(
                     self.wal_log
                         .save(height, LogType::Vote, &rlp::encode(signed_vote))
-                        .or_else(|e| {
-                            Err(BftError::SaveWalErr(format!(
-                                "{:?} of {:?}",
-                                e, signed_vote
-                            )))
-                        }),
+                        .map_err(|e| BftError::SaveWalErr(format!("{:?} of {:?}", e, signed_vote))),
                     &self.params.address,
                 );
             }
             handle_err(result, &self.params.address);
         }
 
-        if height > self.height || round >= self.round + CACHE_N {
+        if height > self.height || round >= self.round + cache_size {
             return Err(BftError::HigherMsg(format!("{:?}", signed_vote)));
         }
 
         Ok(())
     }
 
+    /// A function to emit a quorum event if the vote brings the weight of its block
+    /// (or nil) over the quorum at the current height.
+    fn check_quorum(&mut self, vote: &Vote, vote_weight: u64) {
+        let weight = self
+            .votes
+            .get_voteset(vote.height, vote.round, &vote.vote_type)
+            .and_then(|vote_set| vote_set.votes_by_proposal.get(&vote.block_hash).cloned())
+            .unwrap_or(0);
+        if !self.cal_above_threshold(weight, &vote.vote_type)
+            || self.cal_above_threshold(weight.saturating_sub(vote_weight), &vote.vote_type)
+        {
+            return;
+        }
+        let block_hash = Some(vote.block_hash.clone()).filter(|hash| !hash.0.is_empty());
+        let event = match vote.vote_type {
+            VoteType::Prevote => BftEvent::PrevoteQuorum {
+                height: vote.height,
+                round: vote.round,
+                block_hash,
+            },
+            VoteType::Precommit => BftEvent::PrecommitQuorum {
+                height: vote.height,
+                round: vote.round,
+                block_hash,
+            },
+        };
+        self.emit(event);
+    }
+
     pub(crate) fn check_and_save_status(
         &mut self,
         status: &Status,
@@ -531,19 +698,40 @@ where
             handle_err(
                 self.wal_log
                     .save(self.height + 1, LogType::Proof, &rlp::encode(&self.proof))
-                    .or_else(|e| {
-                        Err(BftError::SaveWalErr(format!(
-                            "{:?} of {:?}",
-                            e, &self.proof
-                        )))
-                    }),
+                    .map_err(|e| BftError::SaveWalErr(format!("{:?} of {:?}", e, &self.proof))),
                 &self.params.address,
             );
+            // only the wal log of the current height is loaded, carry the failed proposers
+            if !self.failed_proposers.is_empty() {
+                handle_err(
+                    self.wal_log
+                        .save(
+                            self.height + 1,
+                            LogType::FailedProposers,
+                            &encode_failed_proposers(&self.failed_proposers),
+                        )
+                        .map_err(|e| BftError::SaveWalErr(format!("{:?} of failed proposers", e))),
+                    &self.params.address,
+                );
+            }
+            // only the wal log of the current height is loaded, carry the state of the election
+            if let Some(state) = self.election_states.get(&status.height) {
+                handle_err(
+                    self.wal_log
+                        .save(
+                            status.height + 1,
+                            LogType::ElectionState,
+                            &encode_election_state(status.height, state),
+                        )
+                        .map_err(|e| BftError::SaveWalErr(format!("{:?} of election state", e))),
+                    &self.params.address,
+                );
+            }
             let status_height = status.height;
             handle_err(
                 self.wal_log
                     .save(status_height + 1, LogType::Status, &rlp::encode(status))
-                    .or_else(|e| Err(BftError::SaveWalErr(format!("{:?} of {:?}", e, status)))),
+                    .map_err(|e| BftError::SaveWalErr(format!("{:?} of {:?}", e, status))),
                 &self.params.address,
             );
         }
@@ -560,7 +748,7 @@ where
             handle_err(
                 self.wal_log
                     .save(self.height, LogType::VerifyResp, &rlp::encode(verify_resp))
-                    .or_else(|_| Err(BftError::SaveWalErr(format!("{:?}", verify_resp)))),
+                    .map_err(|_| BftError::SaveWalErr(format!("{:?}", verify_resp))),
                 &self.params.address,
             );
         }
@@ -586,11 +774,8 @@ where
             handle_err(
                 self.wal_log
                     .save(height, LogType::Feed, &rlp::encode(feed))
-                    .or_else(|e| {
-                        Err(BftError::SaveWalErr(format!(
-                            "{:?} of feed with height {}",
-                            e, height
-                        )))
+                    .map_err(|e| {
+                        BftError::SaveWalErr(format!("{:?} of feed with height {}", e, height))
                     }),
                 &self.params.address,
             );
@@ -602,6 +787,72 @@ where
         Ok(())
     }
 
+    /// A function to keep a conflicting proposal of the current height, since the others may
+    /// lock its block. Its block is checked at once like a proposal without `verify_req`,
+    /// otherwise the verify result of the round would be mixed up, so it is not kept.
+    fn check_conflicting_proposal(
+        &mut self,
+        signed_proposal: &SignedProposal,
+        block: &Block,
+        signed_proposal_hash: &[u8],
+        need_wal: bool,
+    ) -> BftResult<()> {
+        let proposal = &signed_proposal.proposal;
+        if self.params.config.verify_req
+            || self.params.config.compact_block
+            || self
+                .proposals
+                .has_conflicting(proposal.height, proposal.round)
+        {
+            return Ok(());
+        }
+        self.check_proposer(proposal)?;
+        self.check_lock_votes(proposal, &proposal.block_hash)?;
+        self.check_proof_only(
+            &proposal.proof,
+            proposal.height,
+            self.get_authorities(proposal.height)?,
+        )?;
+        let verify_resp = self
+            .function
+            .check_block(
+                block,
+                &proposal.block_hash,
+                &self.function.crypt_hash(signed_proposal_hash),
+                (proposal.height, proposal.round),
+                proposal.lock_round.is_some(),
+                &proposal.proposer,
+            )
+            .map_err(|e| BftError::CheckBlockFailed(format!("{:?} of {:?}", e, proposal)))?;
+        if !verify_resp.is_pass {
+            return Err(BftError::CheckBlockFailed(format!("of {:?}", proposal)));
+        }
+
+        if self.proposals.add_conflicting(signed_proposal) {
+            self.blocks
+                .add(proposal.height, &proposal.block_hash, block);
+            if need_wal {
+                self.save_proposal_block(signed_proposal, block);
+            }
+        }
+        Ok(())
+    }
+
+    /// A function to save the proposal with its block, so it can be replayed by itself.
+    fn save_proposal_block(&mut self, signed_proposal: &SignedProposal, block: &Block) {
+        let encode = combine_two(&rlp::encode(signed_proposal), block);
+        handle_err(
+            self.wal_log
+                .save(
+                    signed_proposal.proposal.height,
+                    LogType::ProposalBlock,
+                    &encode,
+                )
+                .map_err(|e| BftError::SaveWalErr(format!("{:?} of {:?}", e, signed_proposal))),
+            &self.params.address,
+        );
+    }
+
     pub(crate) fn check_block_txs(
         &mut self,
         proposal: &Proposal,
@@ -612,58 +863,74 @@ where
         let round = proposal.round;
         let block_hash = &proposal.block_hash;
 
-        #[cfg(not(feature = "verify_req"))]
-        {
+        if !self.params.config.verify_req {
             let verify_resp = self
                 .function
-                .check_block(block, block_hash, signed_proposal_hash, (height, round), proposal.lock_round.is_some(), proposal.proposer)
+                .check_block(
+                    block,
+                    block_hash,
+                    signed_proposal_hash,
+                    (height, round),
+                    proposal.lock_round.is_some(),
+                    &proposal.proposer,
+                )
                 .map_err(|e| BftError::CheckBlockFailed(format!("{:?} of {:?}", e, proposal)))?;
             self.check_and_save_verify_resp(&verify_resp, false)?;
-            if verify_resp.is_pass {
-                return Ok(());
+            return if verify_resp.is_pass {
+                Ok(())
             } else {
-                return Err(BftError::CheckBlockFailed(format!("of {:?}", proposal)));
-            }
+                Err(BftError::CheckBlockFailed(format!("of {:?}", proposal)))
+            };
         }
 
-        #[cfg(feature = "verify_req")]
-        {
-            let function = self.function.clone();
-            let sender = self.msg_sender.clone();
-            let block = block.clone();
-            let block_hash = block_hash.clone();
-            let is_lock = proposal.lock_round.is_some();
-            let signed_proposal_hash = signed_proposal_hash.clone();
-            let address = self.params.address.clone();
-            let proposer = proposal.proposer.clone();
-            thread::spawn(move || {
-                match function.check_block(
-                    &block,
-                    &block_hash,
-                    &signed_proposal_hash,
-                    (height ,round),
-                    is_lock,
-                    &proposer,
-                ) {
-                    Ok(verify_resp) => {
-                        handle_err(
-                            sender
-                                .send(BftMsg::VerifyResp(verify_resp))
-                                .map_err(|e| BftError::SendMsgErr(format!("{:?}", e))),
-                            &address,
-                        );
-                    }
-                    Err(e) => {
-                        warn!(
-                            "Node {:?} encounters BftError::CheckTxsFailed({:?})",
-                            address, e
-                        );
-                    }
-                };
-            });
-
-            Ok(())
+        if self.action_sender.is_some() {
+            self.send_action(BftAction::CheckBlock(CheckBlockReq {
+                block: block.clone(),
+                block_hash: block_hash.clone(),
+                signed_proposal_hash: signed_proposal_hash.clone(),
+                height,
+                round,
+                is_lock: proposal.lock_round.is_some(),
+                proposer: proposal.proposer.clone(),
+            }));
+            return Ok(());
         }
+
+        let function = self.function.clone();
+        let sender = self.msg_sender.clone();
+        let block = block.clone();
+        let block_hash = block_hash.clone();
+        let is_lock = proposal.lock_round.is_some();
+        let signed_proposal_hash = signed_proposal_hash.clone();
+        let address = self.params.address.clone();
+        let proposer = proposal.proposer.clone();
+        thread::spawn(move || {
+            match function.check_block(
+                &block,
+                &block_hash,
+                &signed_proposal_hash,
+                (height, round),
+                is_lock,
+                &proposer,
+            ) {
+                Ok(verify_resp) => {
+                    handle_err(
+                        sender
+                            .send(BftMsg::VerifyResp(verify_resp))
+                            .map_err(|e| BftError::SendMsgErr(format!("{:?}", e))),
+                        &address,
+                    );
+                }
+                Err(e) => {
+                    warn!(
+                        "Node {:?} encounters BftError::CheckTxsFailed({:?})",
+                        address, e
+                    );
+                }
+            };
+        });
+
+        Ok(())
     }
 
     pub(crate) fn check_proof(&mut self, height: Height, proof: &Proof) -> BftResult<()> {
@@ -697,15 +964,14 @@ where
             )));
         }
 
-        let vote_addresses: Vec<Address> = proof
-            .precommit_votes
-            .iter()
-            .map(|(voter, _)| voter.clone())
-            .collect();
+        let vote_addresses: Vec<Address> = proof.precommit_votes.keys().cloned().collect();
 
-        if get_votes_weight(authorities, &vote_addresses) * 3 <= get_total_weight(authorities) * 2 {
+        if !self.params.config.commit_quorum.is_reached(
+            get_votes_weight(authorities, &vote_addresses),
+            get_total_weight(authorities),
+        ) {
             return Err(BftError::CheckProofFailed(format!(
-                "the proof doesn't collect 2/3+ weight \n {:?} ",
+                "the proof doesn't collect the quorum weight \n {:?} ",
                 proof
             )));
         }
@@ -753,7 +1019,7 @@ where
         block_hash: &Hash,
     ) -> BftResult<()> {
         let height = proposal.height;
-        if height < self.height - 1 || height > self.height {
+        if height + 1 < self.height || height > self.height {
             return Err(BftError::ShouldNotHappen(format!(
                 "check_lock_votes for {:?}",
                 proposal
@@ -782,11 +1048,14 @@ where
             .map(|signed_vote| signed_vote.vote.voter.clone())
             .collect();
 
-        if get_votes_weight(authorities, &vote_addresses) * 3 > get_total_weight(authorities) * 2 {
+        if self.params.config.lock_quorum.is_reached(
+            get_votes_weight(authorities, &vote_addresses),
+            get_total_weight(authorities),
+        ) {
             return Ok(());
         }
         Err(BftError::CheckLockVotesFailed(format!(
-            "less than 2/3+ weight of {:?} with lock_votes {:?}",
+            "less than the quorum weight of {:?} with lock_votes {:?}",
             proposal, &proposal.lock_votes
         )))
     }
@@ -798,7 +1067,7 @@ where
         block_hash: &Hash,
         signed_vote: &SignedVote,
     ) -> BftResult<Address> {
-        if height < self.height - 1 {
+        if height + 1 < self.height {
             return Err(BftError::ShouldNotHappen(format!(
                 "check_vote for {:?}",
                 signed_vote
@@ -847,23 +1116,106 @@ where
             )));
         }
 
-        let vote_weight = self.get_vote_weight(height, &voter);
-        let _ = self.votes.add(&signed_vote, vote_weight, self.height);
+        let vote_weight = self.get_vote_weight(height, voter);
+        let result = self.votes.add(signed_vote, vote_weight, self.height);
+        self.check_and_save_evidence(&result, true);
         Ok(address)
     }
 
+    /// A function to save the evidence carried by an equivocation error, return `true` if it is new.
+    pub(crate) fn check_and_save_evidence(
+        &mut self,
+        result: &BftResult<()>,
+        need_wal: bool,
+    ) -> bool {
+        if let Err(BftError::Equivocation(evidence)) = result {
+            if self.evidences.add(evidence) {
+                self.report_misbehavior(Misbehavior::Equivocation(*evidence.clone()));
+                if need_wal {
+                    handle_err(
+                        self.wal_log
+                            .save(self.height, LogType::Evidence, &rlp::encode(&**evidence))
+                            .map_err(|e| {
+                                BftError::SaveWalErr(format!("{:?} of {:?}", e, evidence))
+                            }),
+                        &self.params.address,
+                    );
+                }
+                return true;
+            }
+        }
+        false
+    }
+
+    /// A function to check that the [`signature`] of [`hash`] is signed by the [`signer`],
+    /// an invalid signature claimed by an authority will be reported.
+    pub(crate) fn check_signer<M: Encodable + Debug>(
+        &self,
+        height: Height,
+        signer: &Address,
+        signature: &Signature,
+        hash: &Hash,
+        signed_msg: &M,
+    ) -> BftResult<()> {
+        let result = self
+            .function
+            .check_sig(signature, hash)
+            .map_err(|e| BftError::CheckSigFailed(format!("{:?} of {:?}", e, signed_msg)))
+            .and_then(|address| {
+                if &address == signer {
+                    Ok(())
+                } else {
+                    Err(BftError::InvalidSender(format!(
+                        "recovers {:?} of {:?}",
+                        address, signed_msg
+                    )))
+                }
+            });
+        if result.is_err() && self.is_authority(height, signer) {
+            self.report_misbehavior(Misbehavior::InvalidSignature {
+                address: signer.clone(),
+                signed_msg: rlp::encode(signed_msg),
+            });
+        }
+        result
+    }
+
+    #[inline]
+    fn is_authority(&self, height: Height, address: &Address) -> bool {
+        self.get_authorities(height)
+            .map(|authorities| authorities.iter().any(|node| &node.address == address))
+            .unwrap_or(false)
+    }
+
+    pub(crate) fn report_misbehavior(&self, misbehavior: Misbehavior) {
+        warn!(
+            "Node {:?} reports misbehavior {:?}",
+            self.params.address, misbehavior
+        );
+        self.function.report_misbehavior(misbehavior);
+    }
+
     pub(crate) fn check_proposer(&self, proposal: &Proposal) -> BftResult<()> {
         let height = proposal.height;
         let round = proposal.round;
         let address = &proposal.proposer;
 
-        if height < self.height - 1 || height > self.height {
+        if height + 1 < self.height || height > self.height {
             return Err(BftError::ShouldNotHappen(format!(
                 "check_proposer for {:?}",
                 proposal
             )));
         }
-        let proposer = self.get_proposer(height, round)?;
+        // the proof of the proposal is used if the node has not got the proof of the previous height
+        let proof = match self.get_previous_proof(height) {
+            Some(proof) => Some(proof),
+            None if proposal.proof.height + 1 == height => {
+                self.check_proof_only(&proposal.proof, height, self.get_authorities(height)?)?;
+                Some(&proposal.proof)
+            }
+            None => None,
+        };
+        let proposer = self.get_proposer(height, round, proof)?;
         if proposer == address {
             Ok(())
         } else {
@@ -878,7 +1230,7 @@ where
         let height = vote.height;
         let voter = &vote.voter;
 
-        if height < self.height - 1 || height > self.height {
+        if height + 1 < self.height || height > self.height {
             return Err(BftError::ShouldNotHappen(format!(
                 "check_voter for {:?}",
                 vote
@@ -902,8 +1254,8 @@ where
 
         if let Some(ins) = self.height_filter.get(voter) {
             // had received retransmit message from the address
-            if (Instant::now() - *ins)
-                > self.params.timer.get_prevote() * TIMEOUT_LOW_HEIGHT_MESSAGE_COEF
+            if (self.params.clock.now() - *ins)
+                > self.params.timer.get_prevote() * self.params.config.low_height_message_coef
             {
                 trans_flag = true;
             }
@@ -918,7 +1270,7 @@ where
 
         if let Some(ins) = self.round_filter.get(voter) {
             // had received retransmit message from the address
-            if (Instant::now() - *ins)
+            if (self.params.clock.now() - *ins)
                 > self.params.timer.get_prevote() * TIMEOUT_LOW_ROUND_MESSAGE_COEF
             {
                 trans_flag = true;
@@ -937,9 +1289,143 @@ where
             .map_err(|e| BftError::SendMsgErr(format!("{:?} of {:?}", e, info)))
     }
 
+    /// A function to broadcast a msg by the BftSupport,
+    /// or return it as an action if the state machine is step-driven.
+    #[inline]
+    pub(crate) fn transmit(&self, msg: BftMsg) {
+        if self.action_sender.is_some() {
+            self.send_action(BftAction::Broadcast(msg));
+        } else {
+            self.function.transmit(msg);
+        }
+    }
+
+    /// A function to set a timer to sync the wal records of a group commit,
+    /// it is called after a msg or a timeout is processed.
+    pub(crate) fn set_wal_sync_timer(&mut self) {
+        if let Some(delay) = self.wal_log.take_sync_delay() {
+            debug!(
+                "Node {:?} will sync the wal log after {:?}",
+                self.params.address, delay
+            );
+            self.start_timer(delay, self.step, TimerKind::WalSync);
+        }
+    }
+
+    #[inline]
+    pub(crate) fn send_action(&self, action: BftAction) {
+        if let Some(sender) = &self.action_sender {
+            let info = format!("{:?}", &action);
+            handle_err(
+                sender.send(action).map_err(|_| BftError::SendMsgErr(info)),
+                &self.params.address,
+            );
+        }
+    }
+
+    /// A function to send an event to the subscribers,
+    /// the subscribers whose receivers are dropped are removed.
+    pub(crate) fn emit(&mut self, event: BftEvent) {
+        self.event_senders
+            .retain(|sender| sender.send(event.clone()).is_ok());
+    }
+
     #[inline]
     pub(crate) fn change_to_step(&mut self, step: Step) {
+        if self.step != step {
+            self.emit(BftEvent::StepChange {
+                height: self.height,
+                round: self.round,
+                step,
+            });
+        }
         self.step = step;
+        handle_err(
+            self.wal_log
+                .sync_on_step()
+                .map_err(|e| BftError::SaveWalErr(format!("{:?} of sync on {:?}", e, step))),
+            &self.params.address,
+        );
+    }
+
+    /// A function to get the node's own signed_vote of the giving height and round,
+    /// which has been signed before or loaded from the wal log.
+    /// The wal log of the current height is replayed into the vote collector when it is loaded,
+    /// whose votes are known before the replay, while a vote of a lower height is looked up
+    /// in its wal log.
+    pub(crate) fn get_own_vote(
+        &mut self,
+        height: Height,
+        round: Round,
+        vote_type: VoteType,
+    ) -> Option<SignedVote> {
+        let signed_vote = self
+            .votes
+            .get_voteset(height, round, &vote_type)
+            .and_then(|vote_set| vote_set.votes_by_sender.get(&self.params.address).cloned());
+        if signed_vote.is_some() {
+            return signed_vote;
+        }
+
+        let address = self.params.address.clone();
+        let is_own_vote = |signed_vote: &SignedVote| {
+            let vote = &signed_vote.vote;
+            vote.height == height
+                && vote.round == round
+                && vote.vote_type == vote_type
+                && vote.voter == address
+        };
+        if height >= self.wal_log.height() {
+            return self.wal_votes.iter().find(|v| is_own_vote(v)).cloned();
+        }
+
+        let records = match self.wal_log.load_height(height) {
+            Ok(records) => records,
+            Err(e) => {
+                warn!(
+                    "Node {:?} can not load wal log of height {}: {:?}",
+                    self.params.address, height, e
+                );
+                return None;
+            }
+        };
+        records
+            .into_iter()
+            .filter_map(|(log_type, encode)| match log_type {
+                LogType::Vote => rlp::decode::<SignedVote>(&encode).ok(),
+                _ => None,
+            })
+            .find(is_own_vote)
+    }
+
+    /// A function to sign the node's own vote, which is saved and synced before it is returned.
+    pub(crate) fn sign_own_vote(&mut self, vote: &Vote) -> BftResult<SignedVote> {
+        let signed_vote = self.build_signed_vote(vote)?;
+        self.save_own_msg(vote.height, LogType::Vote, &rlp::encode(&signed_vote))?;
+        Ok(signed_vote)
+    }
+
+    /// A function to get the node's own signed_proposal of the current round,
+    /// which has been signed before or loaded from the wal log.
+    pub(crate) fn get_own_proposal(&mut self) -> Option<SignedProposal> {
+        self.proposals
+            .get_proposal(self.height, self.round)
+            .filter(|signed_proposal| signed_proposal.proposal.proposer == self.params.address)
+    }
+
+    /// A function to save the node's own signed_proposal or signed_vote and sync the wal log,
+    /// it must be called before the message is broadcast.
+    pub(crate) fn save_own_msg(
+        &mut self,
+        height: Height,
+        log_type: LogType,
+        encode: &[u8],
+    ) -> BftResult<()> {
+        let info = format!("{:?}", log_type);
+        self.wal_log
+            .save(height, log_type, encode)
+            .and_then(|_| self.wal_log.sync())
+            .map_err(|e| BftError::SaveWalErr(format!("{:?} of own {}", e, info)))
     }
 
     #[inline]
@@ -949,14 +1435,28 @@ where
     }
 
     #[inline]
-    pub(crate) fn cal_above_threshold(&self, count: u64) -> bool {
+    pub(crate) fn cal_above_threshold(&self, count: u64, vote_type: &VoteType) -> bool {
         let weight_sum = get_total_weight(&self.authority_manage.authorities);
-        count * 3 > weight_sum * 2
+        self.get_quorum(vote_type).is_reached(count, weight_sum)
+    }
+
+    /// A function to get the quorum of prevotes to lock, or of precommits to commit.
+    #[inline]
+    pub(crate) fn get_quorum(&self, vote_type: &VoteType) -> Quorum {
+        match vote_type {
+            VoteType::Prevote => self.params.config.lock_quorum,
+            VoteType::Precommit => self.params.config.commit_quorum,
+        }
     }
 
     pub(crate) fn clean_polc(&mut self) {
         self.block_hash = None;
-        self.lock_status = None;
+        if self.lock_status.take().is_some() {
+            self.emit(BftEvent::LockReleased {
+                height: self.height,
+                round: self.round,
+            });
+        }
         debug!(
             "Node {:?} cleans PoLC at h:{}, r:{}",
             self.params.address, self.height, self.round
@@ -969,8 +1469,6 @@ where
         self.block_hash = None;
         self.lock_status = None;
         self.votes.clear_vote_count();
-
-        #[cfg(feature = "verify_req")]
         self.verify_results.clear();
     }
 
@@ -995,17 +1493,22 @@ where
         self.round_filter.clear();
         self.last_commit_round = None;
         self.last_commit_block_hash = None;
-        self.htime = Instant::now();
+        self.htime = self.params.clock.now();
         self.feed = None;
         self.verify_results.clear();
         self.proof = proof;
+        self.failed_proposers.clear();
+        self.election_states.clear();
         self.authority_manage = AuthorityManage::new();
-        self.proposals = ProposalCollector::new();
-        self.votes = VoteCollector::new();
-        //TODO: 将之前的 wal 文件备份
-        let wal_path = &self.wal_log.dir;
-        let _ = fs::remove_dir_all(wal_path);
-        self.wal_log = Wal::new(wal_path).unwrap();
+        self.proposals = ProposalCollector::new(self.params.config.cache_size);
+        self.votes = VoteCollector::new(self.params.config.cache_size);
+        self.evidences = EvidenceCollector::new();
+        handle_err(
+            self.wal_log
+                .clear()
+                .map_err(|e| BftError::SaveWalErr(format!("{:?} of clear", e))),
+            &self.params.address,
+        );
         self.consensus_power = false;
     }
 }
@@ -1065,7 +1568,7 @@ pub fn encode_block(height: Height, block: &Block, block_hash: &Hash) -> Vec<u8>
     let height_mark = height.to_be_bytes();
     let mut encode = Vec::with_capacity(8 + block.0.len());
     encode.extend_from_slice(&height_mark);
-    let combine = combine_two(&block_hash.0, &block);
+    let combine = combine_two(&block_hash.0, block);
     encode.extend_from_slice(&combine);
     encode
 }
@@ -1079,37 +1582,40 @@ pub fn decode_block(encode: &[u8]) -> BftResult<(Height, Block, Hash)> {
     Ok((height, block.into(), block_hash.into()))
 }
 
-#[cfg(feature = "random_proposer")]
-pub(crate) fn get_index(seed: u64, weight: &[u64]) -> usize {
-    let sum: u64 = weight.iter().sum();
-    let x = u64::max_value() / sum;
-
-    let mut rng = Pcg::seed_from_u64(seed);
-    let mut res = rng.next_u64();
-    while res >= sum * x {
-        res = rng.next_u64();
+pub(crate) fn encode_failed_proposers(
+    failed_proposers: &BTreeMap<Height, Vec<Address>>,
+) -> Vec<u8> {
+    let mut s = rlp::RlpStream::new_list(failed_proposers.len());
+    for (height, addresses) in failed_proposers.iter() {
+        s.begin_list(2).append(height).append_list(addresses);
     }
-    let mut acc = 0;
-    for (index, w) in weight.iter().enumerate() {
-        acc += *w;
-        if res < acc * x {
-            return index;
-        }
+    s.out()
+}
+
+pub(crate) fn decode_failed_proposers(encode: &[u8]) -> BftResult<BTreeMap<Height, Vec<Address>>> {
+    let r = rlp::Rlp::new(encode);
+    let mut failed_proposers = BTreeMap::new();
+    for item in r.iter() {
+        let height: Height = item
+            .val_at(0)
+            .map_err(|e| BftError::DecodeErr(format!("failed_proposers encounters {:?}", e)))?;
+        let addresses: Vec<Address> = item
+            .list_at(1)
+            .map_err(|e| BftError::DecodeErr(format!("failed_proposers encounters {:?}", e)))?;
+        failed_proposers.insert(height, addresses);
     }
-    0
+    Ok(failed_proposers)
 }
 
-#[cfg(not(feature = "random_proposer"))]
-pub(crate) fn get_index(seed: u64, weight: &[u64]) -> usize {
-    let sum: u64 = weight.iter().sum();
-    let x = seed % sum;
+pub(crate) fn encode_election_state(height: Height, state: &[u8]) -> Vec<u8> {
+    let mut s = rlp::RlpStream::new_list(2);
+    s.append(&height).append(&state);
+    s.out()
+}
 
-    let mut acc = 0;
-    for (index, w) in weight.iter().enumerate() {
-        acc += *w;
-        if x < acc {
-            return index;
-        }
-    }
-    0
+pub(crate) fn decode_election_state(encode: &[u8]) -> BftResult<(Height, Vec<u8>)> {
+    let r = rlp::Rlp::new(encode);
+    let decode =
+        || -> Result<(Height, Vec<u8>), rlp::DecoderError> { Ok((r.val_at(0)?, r.val_at(1)?)) };
+    decode().map_err(|e| BftError::DecodeErr(format!("election_state encounters {:?}", e)))
 }
@@ -1,112 +1,580 @@
+use crate::clock::Clock;
 use crate::objects::LogType;
-use crate::Height;
+use crate::{Height, WalConfig, WalDurability};
+use crc32fast::Hasher as Crc32;
+use flate2::{write::GzEncoder, Compression};
 #[allow(unused_imports)]
 use log::{log, trace, warn};
 use std::collections::BTreeMap;
-use std::fs::{read_dir, DirBuilder, File, OpenOptions};
+use std::fs::{read_dir, remove_dir_all, remove_file, rename, DirBuilder, File, OpenOptions};
 use std::io::{self, Read, Seek, Write};
-use std::mem::transmute;
+use std::path::Path;
 use std::str;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-const DELETE_FILE_INTERVAL: u64 = 3;
+/// The default number of heights whose logs are kept below the current height.
+pub const DEFAULT_WAL_RETENTION: Height = 3;
 
-pub(crate) struct Wal {
+/// The magic number at the beginning of every wal file.
+const WAL_MAGIC: [u8; 4] = *b"BWAL";
+/// The version of the wal record format.
+const WAL_VERSION: u32 = 2;
+/// The headerless wal files written before the file header was added are version 0,
+/// whose record header is [len u32][type u8] without crc32.
+const LEGACY_RECORD_HEADER_LEN: usize = 5;
+/// The record header of version 1: [len u32][type u8][crc32 u32],
+/// the crc32 covers the type and the payload but not the length.
+const V1_RECORD_HEADER_LEN: usize = 9;
+/// The file header: [magic 4 bytes][version u32].
+pub(crate) const FILE_HEADER_LEN: usize = 8;
+/// The record header: [len u32][type u8][crc32 u32][header crc32 u32],
+/// the crc32 covers the length, the type and the payload,
+/// and the header crc32 covers the rest of the header, so the length is checked
+/// before the payload is read.
+const RECORD_HEADER_LEN: usize = 13;
+
+/// Define the storage backend of the wal log.
+/// The storage keeps an append-only byte log for each height and the current height,
+/// the records are framed and checked by the wal log itself.
+pub trait WalStorage: Send {
+    /// A function to get the current height, which is 0 if it has never been set.
+    fn current_height(&mut self) -> io::Result<Height>;
+
+    /// A function to set the current height.
+    fn set_current_height(&mut self, height: Height) -> io::Result<()>;
+
+    /// A function to append data to the log of the height.
+    fn append(&mut self, height: Height, data: &[u8]) -> io::Result<()>;
+
+    /// A function to make all appended data durable.
+    fn sync(&mut self) -> io::Result<()>;
+
+    /// A function to load the whole log of the height, which is empty if there is none.
+    fn load(&mut self, height: Height) -> io::Result<Vec<u8>>;
+
+    /// A function to shorten the log of the height to the length.
+    fn set_len(&mut self, height: Height, len: u64) -> io::Result<()>;
+
+    /// A function to remove (or archive) the logs below the height.
+    fn truncate_below(&mut self, height: Height) -> io::Result<()>;
+
+    /// A function to remove (or archive) all logs and the current height.
+    fn clear(&mut self) -> io::Result<()>;
+}
+
+/// A function to check the magic number and the version of a wal file header.
+pub(crate) fn check_file_header(header: &[u8], path: &str) -> io::Result<()> {
+    if header.len() < FILE_HEADER_LEN || header[0..4] != WAL_MAGIC {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("wal file {} has no valid header", path),
+        ));
+    }
+    let mut version: [u8; 4] = [0; 4];
+    version.copy_from_slice(&header[4..8]);
+    let version = u32::from_le_bytes(version);
+    if version != WAL_VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("wal file {} has unsupported version {}", path, version),
+        ));
+    }
+    Ok(())
+}
+
+/// A function to frame a record of the type with its header.
+fn encode_record(mtype: u8, msg: &[u8]) -> Vec<u8> {
+    let len = (msg.len() as u32).to_le_bytes();
+    let mut crc = Crc32::new();
+    crc.update(&len);
+    crc.update(&[mtype]);
+    crc.update(msg);
+
+    let mut record = Vec::with_capacity(RECORD_HEADER_LEN + msg.len());
+    record.extend_from_slice(&len);
+    record.push(mtype);
+    record.extend_from_slice(&crc.finalize().to_le_bytes());
+    let mut header_crc = Crc32::new();
+    header_crc.update(&record);
+    record.extend_from_slice(&header_crc.finalize().to_le_bytes());
+    record.extend_from_slice(msg);
+    record
+}
+
+/// A wal storage keeping one file for each height under a directory.
+/// Old files are deleted, or compressed into the archive directory if it is set.
+pub struct FileWalStorage {
     height_fs: BTreeMap<Height, File>,
-    pub(crate) dir: String,
-    current_height: Height,
-    ifile: File, // store off-line height
+    dir: String,
+    archive_dir: Option<String>,
 }
 
-impl Wal {
-    pub(crate) fn new(dir: &str) -> Result<Wal, io::Error> {
-        let fss = read_dir(&dir);
-        if fss.is_err() {
-            DirBuilder::new()
-                .recursive(true)
-                .create(dir)
-                .expect("Create wal directory failed!");
-        }
-
-        let file_path = dir.to_string() + "/" + "index";
-        let expect_str = format!("Seek wal file {:?} failed!", &file_path);
-        let mut ifs = OpenOptions::new()
+impl FileWalStorage {
+    /// A function to create a file wal storage under the directory.
+    pub fn new(dir: &str) -> io::Result<Self> {
+        if read_dir(dir).is_err() {
+            DirBuilder::new().recursive(true).create(dir)?;
+        }
+
+        Ok(FileWalStorage {
+            height_fs: BTreeMap::new(),
+            dir: dir.to_string(),
+            archive_dir: None,
+        })
+    }
+
+    /// A function to create a file wal storage under the directory,
+    /// which archives old files into the archive directory instead of deleting them.
+    pub fn with_archive(dir: &str, archive_dir: &str) -> io::Result<Self> {
+        let mut storage = FileWalStorage::new(dir)?;
+        storage.archive_dir = Some(archive_dir.to_string());
+        Ok(storage)
+    }
+
+    /// A function to compress the file into `{dst_dir}/{file_name}.gz`.
+    fn archive_file(src: &Path, dst_dir: &str) -> io::Result<()> {
+        DirBuilder::new().recursive(true).create(dst_dir)?;
+        let file_name = src
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "invalid wal file name"))?;
+        // never overwrite an archive of the same height, e.g. the height is archived again
+        // after the node is cleared
+        let mut dst_path = format!("{}/{}.gz", dst_dir, file_name);
+        let mut count = 0;
+        let dst = loop {
+            match OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&dst_path)
+            {
+                Err(ref e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    count += 1;
+                    dst_path = format!("{}/{}.{}.gz", dst_dir, file_name, count);
+                }
+                result => break result?,
+            }
+        };
+        let mut encoder = GzEncoder::new(dst, Compression::default());
+        io::copy(&mut File::open(src)?, &mut encoder)?;
+        encoder.finish()?.sync_all()
+    }
+
+    /// A function to create a new directory for the archives of a clear,
+    /// which is unique even if the node is cleared twice at once.
+    fn create_clear_dir(archive_dir: &str) -> io::Result<String> {
+        DirBuilder::new().recursive(true).create(archive_dir)?;
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or(0);
+        let mut dst_dir = format!("{}/clear-{}", archive_dir, nanos);
+        let mut count = 0;
+        loop {
+            match DirBuilder::new().create(&dst_dir) {
+                Err(ref e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    count += 1;
+                    dst_dir = format!("{}/clear-{}-{}", archive_dir, nanos, count);
+                }
+                result => return result.map(|_| dst_dir),
+            }
+        }
+    }
+
+    /// A function to delete the file, or archive it if the archive directory is set.
+    fn remove_or_archive(&self, src: &Path, dst_dir: &str) -> io::Result<()> {
+        if self.archive_dir.is_some() {
+            FileWalStorage::archive_file(src, dst_dir)?;
+        }
+        remove_file(src)
+    }
+
+    fn get_index_path(&self) -> String {
+        self.dir.clone() + "/" + "index"
+    }
+
+    /// A function to get the heights of all wal files in the directory.
+    fn get_file_heights(&self) -> io::Result<Vec<Height>> {
+        let mut heights = Vec::new();
+        for entry in read_dir(&self.dir)? {
+            let path = entry?.path();
+            let file_height = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_suffix(".log"))
+                .and_then(|name| name.parse::<Height>().ok());
+            if let Some(file_height) = file_height {
+                heights.push(file_height);
+            }
+        }
+        Ok(heights)
+    }
+
+    /// A function to recover the height when the index is missing or corrupt.
+    /// The wal log moves to a height after it saves the status of the previous height
+    /// in the log of the height, while the msgs of the next height may be saved earlier,
+    /// so the height is the highest one whose log has a status.
+    fn recover_height(&mut self) -> io::Result<Height> {
+        let mut heights = self.get_file_heights()?;
+        heights.sort_unstable_by(|a, b| b.cmp(a));
+        for height in heights {
+            let buf = self.load(height)?;
+            let mut index = 0;
+            while let Ok(Some((mtype, _, body_end))) = Wal::decode_record(&buf, index, height) {
+                if mtype == u8::from(LogType::Status) {
+                    return Ok(height);
+                }
+                index = body_end;
+            }
+        }
+        Ok(0)
+    }
+
+    fn get_file_path(dir: &str, height: Height) -> String {
+        let mut name = height.to_string();
+        name += ".log";
+        let pathname = dir.to_string() + "/";
+        pathname.clone() + &*name
+    }
+
+    fn get_file(&mut self, height: Height) -> io::Result<&mut File> {
+        if !self.height_fs.contains_key(&height) {
+            let filename = FileWalStorage::get_file_path(&self.dir, height);
+            let fs = FileWalStorage::open_log_file(&filename)?;
+            self.height_fs.insert(height, fs);
+        }
+        Ok(self.height_fs.get_mut(&height).unwrap())
+    }
+
+    /// A function to open a wal file, the file header will be written if the file is new,
+    /// or checked if the file exists.
+    fn open_log_file(path: &str) -> io::Result<File> {
+        let mut fs = OpenOptions::new()
             .read(true)
             .create(true)
+            .truncate(false)
             .write(true)
-            .open(file_path)?;
+            .open(path)?;
 
-        ifs.seek(io::SeekFrom::Start(0)).expect(&expect_str);
+        let mut header = Vec::with_capacity(FILE_HEADER_LEN);
+        fs.seek(io::SeekFrom::Start(0))?;
+        (&mut fs)
+            .take(FILE_HEADER_LEN as u64)
+            .read_to_end(&mut header)?;
+
+        let mut expect = WAL_MAGIC.to_vec();
+        expect.extend_from_slice(&WAL_VERSION.to_le_bytes());
+        if header.len() < FILE_HEADER_LEN && expect.starts_with(&header) {
+            // a new file or a torn header, which has no record yet
+            if !header.is_empty() {
+                warn!("Wal file {} has a torn header, rewrite it", path);
+            }
+            fs.set_len(0)?;
+            fs.seek(io::SeekFrom::Start(0))?;
+            fs.write_all(&expect)?;
+            fs.sync_data()?;
+            return Ok(fs);
+        }
 
-        let mut string_buf: String = String::new();
-        let res_fsize = ifs.read_to_string(&mut string_buf)?;
-        let cur_height: Height;
-        let last_file_path: String;
-        if res_fsize == 0 {
-            last_file_path = dir.to_string() + "/0.log";
-            cur_height = 0;
+        if !header.starts_with(&WAL_MAGIC) {
+            return FileWalStorage::migrate_file(fs, path, 0);
+        }
+        if header[4..] == 1u32.to_le_bytes() {
+            return FileWalStorage::migrate_file(fs, path, 1);
+        }
+        check_file_header(&header, path)?;
+        Ok(fs)
+    }
+
+    /// A function to rewrite a wal file of version 0 (headerless) or 1 in the current format,
+    /// the new file replaces the old one by a rename, and a torn tail is dropped.
+    fn migrate_file(mut fs: File, path: &str, version: u32) -> io::Result<File> {
+        let mut old = Vec::new();
+        fs.seek(io::SeekFrom::Start(0))?;
+        fs.read_to_end(&mut old)?;
+        let (body, header_len) = if version == 0 {
+            (&old[..], LEGACY_RECORD_HEADER_LEN)
         } else {
-            let hi_res = string_buf.parse::<Height>();
-            if let Ok(hi) = hi_res {
-                cur_height = hi;
-                last_file_path = dir.to_string() + "/" + cur_height.to_string().as_str() + ".log"
+            (&old[FILE_HEADER_LEN..], V1_RECORD_HEADER_LEN)
+        };
+
+        let mut buf = WAL_MAGIC.to_vec();
+        buf.extend_from_slice(&WAL_VERSION.to_le_bytes());
+        let mut index = 0;
+        while index + header_len <= body.len() {
+            let mut len: [u8; 4] = [0; 4];
+            len.copy_from_slice(&body[index..index + 4]);
+            let mtype = body[index + 4];
+            let body_start = index + header_len;
+            let body_end = body_start.saturating_add(u32::from_le_bytes(len) as usize);
+            if body_end > body.len() {
+                break;
+            }
+            let valid = if version == 0 {
+                mtype <= u8::from(LogType::Block)
             } else {
+                let mut crc: [u8; 4] = [0; 4];
+                crc.copy_from_slice(&body[index + 5..index + 9]);
+                let mut hasher = Crc32::new();
+                hasher.update(&[mtype]);
+                hasher.update(&body[body_start..body_end]);
+                hasher.finalize() == u32::from_le_bytes(crc)
+            };
+            if !valid && body_end == body.len() {
+                // the last record is partially written
+                break;
+            }
+            if !valid {
                 return Err(io::Error::new(
                     io::ErrorKind::InvalidData,
-                    "index file data wrong",
+                    format!(
+                        "wal file {} of version {} is corrupted at offset {}",
+                        path, version, index
+                    ),
                 ));
             }
+            buf.extend_from_slice(&encode_record(mtype, &body[body_start..body_end]));
+            index = body_end;
+        }
+        if index < body.len() {
+            warn!(
+                "Wal file {} of version {} has a torn tail of {} bytes, drop it",
+                path,
+                version,
+                body.len() - index
+            );
         }
 
-        let fs = OpenOptions::new()
-            .read(true)
-            .create(true)
-            .write(true)
-            .open(last_file_path)?;
+        warn!(
+            "Wal file {} is version {}, rewrite it in version {}",
+            path, version, WAL_VERSION
+        );
+        let tmp_path = path.to_string() + ".tmp";
+        {
+            let mut tmp = File::create(&tmp_path)?;
+            tmp.write_all(&buf)?;
+            tmp.sync_data()?;
+        }
+        rename(&tmp_path, path)?;
+        if let Some(dir) = Path::new(path).parent() {
+            // make the rename durable
+            File::open(dir)?.sync_all()?;
+        }
+        FileWalStorage::open_log_file(path)
+    }
+}
+
+impl WalStorage for FileWalStorage {
+    fn current_height(&mut self) -> io::Result<Height> {
+        let index = match ::std::fs::read_to_string(self.get_index_path()) {
+            Ok(content) => content.parse::<Height>().ok(),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e),
+        };
+        if let Some(height) = index {
+            return Ok(height);
+        }
+
+        let height = self.recover_height()?;
+        if height != 0 {
+            warn!(
+                "Wal index in {} is missing or corrupt, recover height {} from wal files",
+                self.dir, height
+            );
+            self.set_current_height(height)?;
+        }
+        Ok(height)
+    }
+
+    /// The index is written to a temporary file and renamed, so it is never seen half written.
+    fn set_current_height(&mut self, height: Height) -> io::Result<()> {
+        let index_path = self.get_index_path();
+        let tmp_path = index_path.clone() + ".tmp";
+        {
+            let mut tmp = OpenOptions::new()
+                .create(true)
+                .truncate(true)
+                .write(true)
+                .open(&tmp_path)?;
+            tmp.write_all(height.to_string().as_bytes())?;
+            tmp.sync_data()?;
+        }
+        rename(&tmp_path, &index_path)?;
+        // make the rename durable
+        File::open(&self.dir)?.sync_all()
+    }
+
+    fn append(&mut self, height: Height, data: &[u8]) -> io::Result<()> {
+        let fs = self.get_file(height)?;
+        fs.seek(io::SeekFrom::End(0))?;
+        fs.write_all(data)?;
+        fs.flush()
+    }
 
-        let mut tmp = BTreeMap::new();
-        tmp.insert(cur_height, fs);
+    fn sync(&mut self) -> io::Result<()> {
+        for fs in self.height_fs.values() {
+            fs.sync_data()?;
+        }
+        Ok(())
+    }
+
+    fn load(&mut self, height: Height) -> io::Result<Vec<u8>> {
+        let fs = self.get_file(height)?;
+        let mut vec_buf: Vec<u8> = Vec::new();
+        fs.seek(io::SeekFrom::Start(FILE_HEADER_LEN as u64))?;
+        fs.read_to_end(&mut vec_buf)?;
+        Ok(vec_buf)
+    }
 
+    fn set_len(&mut self, height: Height, len: u64) -> io::Result<()> {
+        let fs = self.get_file(height)?;
+        fs.set_len(FILE_HEADER_LEN as u64 + len)?;
+        fs.sync_data()
+    }
+
+    fn truncate_below(&mut self, height: Height) -> io::Result<()> {
+        self.height_fs = self.height_fs.split_off(&height);
+        let archive_dir = self.archive_dir.clone().unwrap_or_default();
+        for file_height in self.get_file_heights()? {
+            if file_height < height {
+                let path = FileWalStorage::get_file_path(&self.dir, file_height);
+                self.remove_or_archive(Path::new(&path), &archive_dir)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn clear(&mut self) -> io::Result<()> {
+        self.height_fs.clear();
+        if let Some(archive_dir) = self.archive_dir.clone() {
+            // keep the whole wal log before clearing in a separate directory
+            let dst_dir = FileWalStorage::create_clear_dir(&archive_dir)?;
+            for entry in read_dir(&self.dir)? {
+                let path = entry?.path();
+                if path.is_file() {
+                    FileWalStorage::archive_file(&path, &dst_dir)?;
+                }
+            }
+        }
+        let _ = remove_dir_all(&self.dir);
+        let archive_dir = self.archive_dir.take();
+        *self = FileWalStorage::new(&self.dir)?;
+        self.archive_dir = archive_dir;
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct MemoryLogs {
+    current_height: Height,
+    logs: BTreeMap<Height, Vec<u8>>,
+}
+
+/// A wal storage keeping all logs in the memory.
+/// The clones share the same logs, so a restarted node can load what it saved before.
+#[derive(Clone, Default)]
+pub struct MemoryWalStorage(Arc<Mutex<MemoryLogs>>);
+
+impl MemoryWalStorage {
+    /// A function to create an empty memory wal storage.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn logs(&self) -> io::Result<MutexGuard<'_, MemoryLogs>> {
+        self.0
+            .lock()
+            .map_err(|_| io::Error::other("memory wal storage is poisoned"))
+    }
+}
+
+impl WalStorage for MemoryWalStorage {
+    fn current_height(&mut self) -> io::Result<Height> {
+        Ok(self.logs()?.current_height)
+    }
+
+    fn set_current_height(&mut self, height: Height) -> io::Result<()> {
+        self.logs()?.current_height = height;
+        Ok(())
+    }
+
+    fn append(&mut self, height: Height, data: &[u8]) -> io::Result<()> {
+        self.logs()?
+            .logs
+            .entry(height)
+            .or_default()
+            .extend_from_slice(data);
+        Ok(())
+    }
+
+    fn sync(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn load(&mut self, height: Height) -> io::Result<Vec<u8>> {
+        Ok(self.logs()?.logs.get(&height).cloned().unwrap_or_default())
+    }
+
+    fn set_len(&mut self, height: Height, len: u64) -> io::Result<()> {
+        if let Some(log) = self.logs()?.logs.get_mut(&height) {
+            log.truncate(len as usize);
+        }
+        Ok(())
+    }
+
+    fn truncate_below(&mut self, height: Height) -> io::Result<()> {
+        let mut logs = self.logs()?;
+        logs.logs = logs.logs.split_off(&height);
+        Ok(())
+    }
+
+    fn clear(&mut self) -> io::Result<()> {
+        *self.logs()? = MemoryLogs::default();
+        Ok(())
+    }
+}
+
+pub(crate) struct Wal {
+    storage: Box<dyn WalStorage>,
+    current_height: Height,
+    pub(crate) config: WalConfig,
+    need_sync: bool,
+    last_sync: Instant,
+    sync_timer_set: bool,
+    clock: Arc<dyn Clock>,
+}
+
+impl Wal {
+    pub(crate) fn new(
+        mut storage: Box<dyn WalStorage>,
+        config: WalConfig,
+        clock: Arc<dyn Clock>,
+    ) -> Result<Wal, io::Error> {
+        let current_height = storage.current_height()?;
         Ok(Wal {
-            height_fs: tmp,
-            dir: dir.to_string(),
-            current_height: cur_height,
-            ifile: ifs,
+            storage,
+            current_height,
+            config,
+            need_sync: false,
+            last_sync: clock.now(),
+            sync_timer_set: false,
+            clock,
         })
     }
 
-    fn get_file_path(dir: &str, height: Height) -> String {
-        let mut name = height.to_string();
-        name += ".log";
-        let pathname = dir.to_string() + "/";
-        pathname.clone() + &*name
+    /// A function to get the height of the wal log being written.
+    pub(crate) fn height(&self) -> Height {
+        self.current_height
     }
 
     pub(crate) fn set_height(&mut self, height: Height) -> Result<(), io::Error> {
         self.current_height = height;
-        self.ifile.seek(io::SeekFrom::Start(0))?;
-        let hstr = height.to_string();
-        let content = hstr.as_bytes();
-        let _ = self.ifile.set_len(content.len() as u64);
-        self.ifile.write_all(&content)?;
-        self.ifile.sync_data()?;
-
-        let filename = Wal::get_file_path(&self.dir, height);
-        let fs = OpenOptions::new()
-            .create(true)
-            .read(true)
-            .write(true)
-            .open(filename)?;
-        self.height_fs.insert(height, fs);
+        self.storage.set_current_height(height)?;
 
-        if height > DELETE_FILE_INTERVAL {
-            let saved_height_fs = self.height_fs.split_off(&(height - DELETE_FILE_INTERVAL));
-            {
-                for (height, _) in self.height_fs.iter() {
-                    let delfilename = Wal::get_file_path(&self.dir, *height);
-                    let _ = ::std::fs::remove_file(delfilename);
-                }
-            }
-            self.height_fs = saved_height_fs;
+        if height > self.config.retention {
+            self.storage
+                .truncate_below(height - self.config.retention)?;
         }
 
         Ok(())
@@ -114,92 +582,180 @@ impl Wal {
 
     pub(crate) fn save(&mut self, height: Height, mtype: LogType, msg: &[u8]) -> io::Result<()> {
         trace!("Wal save mtype: {:?}, height: {}", mtype, height);
-        if !self.height_fs.contains_key(&height) {
-            // 2 more higher than current height, do not process it
-            if height > self.current_height + 1 {
-                return Ok(());
-            } else if height == self.current_height + 1 {
-                let filename = Wal::get_file_path(&self.dir, height);
-                let fs = OpenOptions::new()
-                    .read(true)
-                    .create(true)
-                    .write(true)
-                    .open(filename)?;
-                self.height_fs.insert(height, fs);
+        // 2 more higher than current height, do not process it
+        if height > self.current_height + 1 {
+            return Ok(());
+        }
+        // the votes of the last height may be retransmitted, whose log is kept
+        if height + self.config.retention < self.current_height {
+            warn!("Can't find wal log in height {} ", height);
+            return Ok(());
+        }
+        if msg.is_empty() {
+            return Ok(());
+        }
+
+        self.storage
+            .append(height, &encode_record(mtype.into(), msg))?;
+        self.need_sync = true;
+
+        match self.config.durability {
+            WalDurability::EveryRecord => self.sync(),
+            WalDurability::GroupCommit(interval)
+                if self.clock.now().saturating_duration_since(self.last_sync)
+                    >= Duration::from_millis(interval) =>
+            {
+                self.sync()
             }
+            _ => Ok(()),
         }
-        let mlen = msg.len() as u32;
-        if mlen == 0 {
+    }
+
+    /// A function to sync all unsynced records to the storage.
+    pub(crate) fn sync(&mut self) -> io::Result<()> {
+        if !self.need_sync || self.config.durability == WalDurability::NoSync {
             return Ok(());
         }
+        self.storage.sync()?;
+        self.need_sync = false;
+        self.last_sync = self.clock.now();
+        Ok(())
+    }
 
-        if let Some(fs) = self.height_fs.get_mut(&height) {
-            let len_bytes: [u8; 4] = unsafe { transmute(mlen.to_le()) };
-            let mtype: u8 = mtype.into();
-            let type_bytes: [u8; 1] = unsafe { transmute(mtype.to_le()) };
-            fs.seek(io::SeekFrom::End(0))?;
-            fs.write_all(&len_bytes[..])?;
-            fs.write_all(&type_bytes[..])?;
-            fs.write_all(&msg)?;
-            fs.flush()?;
-        } else {
-            warn!("Can't find wal log in height {} ", height);
+    /// A function to get the delay of a timer to sync the records of a group commit,
+    /// which are not synced if no record follows them.
+    /// It returns `None` if the records are synced or the timer has been set.
+    pub(crate) fn take_sync_delay(&mut self) -> Option<Duration> {
+        match self.config.durability {
+            WalDurability::GroupCommit(interval) if self.need_sync && !self.sync_timer_set => {
+                self.sync_timer_set = true;
+                let interval = Duration::from_millis(interval);
+                Some(
+                    interval
+                        .checked_sub(self.clock.now().saturating_duration_since(self.last_sync))
+                        .unwrap_or_default(),
+                )
+            }
+            _ => None,
+        }
+    }
+
+    /// A function to sync the records of a group commit when the timer fires.
+    pub(crate) fn sync_on_timer(&mut self) -> io::Result<()> {
+        self.sync_timer_set = false;
+        self.sync()
+    }
+
+    /// A function to sync the wal log when the node changes its step.
+    pub(crate) fn sync_on_step(&mut self) -> io::Result<()> {
+        if self.config.durability == WalDurability::StepTransition {
+            return self.sync();
         }
+        Ok(())
+    }
 
+    /// A function to remove all records.
+    pub(crate) fn clear(&mut self) -> io::Result<()> {
+        self.storage.clear()?;
+        self.current_height = 0;
+        self.need_sync = false;
         Ok(())
     }
 
-    pub(crate) fn load(&mut self) -> Vec<(LogType, Vec<u8>)> {
-        let mut vec_buf: Vec<u8> = Vec::new();
+    /// A function to load records of the current height.
+    pub(crate) fn load(&mut self) -> io::Result<Vec<(LogType, Vec<u8>)>> {
+        if self.current_height == 0 {
+            return Ok(Vec::new());
+        }
+        self.load_height(self.current_height)
+    }
+
+    /// A function to load records of the height.
+    /// A torn record at the end of a log is truncated, while a corrupted record
+    /// followed by other records returns an error.
+    pub(crate) fn load_height(&mut self, height: Height) -> io::Result<Vec<(LogType, Vec<u8>)>> {
         let mut vec_out: Vec<(LogType, Vec<u8>)> = Vec::new();
-        let cur_height = self.current_height;
-        if self.height_fs.is_empty() || cur_height == 0 {
-            return vec_out;
+        let vec_buf = self.storage.load(height)?;
+        let fsize = vec_buf.len();
+        let mut index = 0;
+        while let Some(record) = Wal::decode_record(&vec_buf, index, height)? {
+            let (mtype, body_start, body_end) = record;
+            vec_out.push((LogType::from(mtype), vec_buf[body_start..body_end].to_vec()));
+            index = body_end;
         }
 
-        for (height, mut fs) in &self.height_fs {
-            if *height < self.current_height {
-                continue;
-            }
-            let expect_str = format!("Seek wal file {:?} of height {} failed!", fs, *height);
-            fs.seek(io::SeekFrom::Start(0)).expect(&expect_str);
-            let res_fsize = fs.read_to_end(&mut vec_buf);
-            if res_fsize.is_err() {
-                return vec_out;
-            }
-            let expect_str = format!(
-                "Get size of buf of wal file {:?} of height {} failed!",
-                fs, *height
+        if index < fsize {
+            warn!(
+                "Wal log of height {} has a torn tail of {} bytes, truncate it",
+                height,
+                fsize - index
             );
-            let fsize = res_fsize.expect(&expect_str);
-            if fsize <= 5 {
-                return vec_out;
-            }
-            let mut index = 0;
-            loop {
-                if index + 5 > fsize {
-                    break;
-                }
-                let hd: [u8; 4] = [
-                    vec_buf[index],
-                    vec_buf[index + 1],
-                    vec_buf[index + 2],
-                    vec_buf[index + 3],
-                ];
-                let tmp: u32 = unsafe { transmute::<[u8; 4], u32>(hd) };
-                let bodylen = tmp as usize;
-                let mtype = vec_buf[index + 4];
-                index += 5;
-                if index + bodylen > fsize {
-                    break;
-                }
-                vec_out.push((
-                    LogType::from(mtype),
-                    vec_buf[index..index + bodylen].to_vec(),
-                ));
-                index += bodylen;
+            self.storage.set_len(height, index as u64)?;
+        }
+        Ok(vec_out)
+    }
+
+    /// A function to decode the record at [`index`], return `None` for a clean end or a torn tail.
+    /// Only the last record may be torn, a corrupted record before the end returns an error.
+    pub(crate) fn decode_record(
+        buf: &[u8],
+        index: usize,
+        height: Height,
+    ) -> io::Result<Option<(u8, usize, usize)>> {
+        let fsize = buf.len();
+        if index + RECORD_HEADER_LEN > fsize {
+            return Ok(None);
+        }
+        let corrupted = || {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "wal log of height {} is corrupted at offset {}",
+                    height, index
+                ),
+            )
+        };
+
+        let header = &buf[index..index + RECORD_HEADER_LEN];
+        let mut header_crc: [u8; 4] = [0; 4];
+        header_crc.copy_from_slice(&header[9..13]);
+        let mut hasher = Crc32::new();
+        hasher.update(&header[..9]);
+        if hasher.finalize() != u32::from_le_bytes(header_crc) {
+            // the tail of a torn write may be left zeroed
+            if buf[index..].iter().all(|byte| *byte == 0) {
+                return Ok(None);
             }
+            return Err(corrupted());
+        }
+
+        let mut len: [u8; 4] = [0; 4];
+        len.copy_from_slice(&header[0..4]);
+        let bodylen = u32::from_le_bytes(len) as usize;
+        let mtype = header[4];
+        let mut crc: [u8; 4] = [0; 4];
+        crc.copy_from_slice(&header[5..9]);
+        let crc = u32::from_le_bytes(crc);
+
+        let body_start = index + RECORD_HEADER_LEN;
+        let body_end = body_start.saturating_add(bodylen);
+        if body_end > fsize {
+            // the length is checked, so the last record is short
+            return Ok(None);
+        }
+
+        let mut hasher = Crc32::new();
+        hasher.update(&len);
+        hasher.update(&[mtype]);
+        hasher.update(&buf[body_start..body_end]);
+        let valid = hasher.finalize() == crc && mtype <= u8::from(LogType::ElectionState);
+        if valid {
+            return Ok(Some((mtype, body_start, body_end)));
+        }
+        if body_end == fsize {
+            // the last record is partially written
+            return Ok(None);
         }
-        vec_out
+        Err(corrupted())
     }
 }
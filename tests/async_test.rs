@@ -0,0 +1,288 @@
+#![cfg(feature = "async")]
+
+use async_trait::async_trait;
+use bft_rs::asynchronous::{AsyncBftActuator, AsyncBftSupport};
+use bft_rs::error::BftError;
+use bft_rs::params::BftConfig;
+use bft_rs::wal::{MemoryWalStorage, WalStorage};
+use bft_rs::*;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+use tokio::time::{sleep, timeout};
+
+const NODE_NUM: usize = 4;
+const INTERVAL: u64 = 1000;
+
+#[derive(Debug)]
+struct TestError;
+
+struct Support {
+    address: Address,
+    authority_list: Vec<Node>,
+    msg_send: UnboundedSender<(BftMsg, Address)>,
+    commit_send: UnboundedSender<(Commit, Address)>,
+}
+
+#[async_trait]
+impl AsyncBftSupport for Support {
+    type Error = TestError;
+
+    async fn check_block(
+        &self,
+        block: &Block,
+        _block_hash: &Hash,
+        _signed_proposal_hash: &Hash,
+        height_round: (Height, Round),
+        _is_lock: bool,
+        _proposer: &Address,
+    ) -> Result<VerifyResp, TestError> {
+        sleep(Duration::from_millis(10)).await;
+        Ok(VerifyResp {
+            is_pass: !block.is_empty(),
+            round: height_round.1,
+            complete_block: Some(block.clone()),
+        })
+    }
+
+    fn transmit(&self, msg: BftMsg) {
+        self.msg_send.send((msg, self.address.clone())).unwrap();
+    }
+
+    async fn commit(&self, commit: Commit) -> Result<Status, TestError> {
+        // executing the block takes a while
+        sleep(Duration::from_millis(50)).await;
+        let height = commit.height;
+        self.commit_send
+            .send((commit, self.address.clone()))
+            .unwrap();
+        Ok(Status {
+            height,
+            interval: Some(INTERVAL),
+            authority_list: self.authority_list.clone(),
+        })
+    }
+
+    async fn get_block(&self, height: Height) -> Result<(Block, Hash), TestError> {
+        sleep(Duration::from_millis(10)).await;
+        let block: Block = format!("block {} of {:?}", height, self.address)
+            .into_bytes()
+            .into();
+        let block_hash = Sha256::digest(&block).to_vec().into();
+        Ok((block, block_hash))
+    }
+
+    fn sign(&self, _hash: &Hash) -> Result<Signature, TestError> {
+        Ok(self.address.to_vec().into())
+    }
+
+    fn check_sig(&self, signature: &Signature, _hash: &Hash) -> Result<Address, TestError> {
+        Ok(signature.to_vec().into())
+    }
+
+    fn crypt_hash(&self, msg: &[u8]) -> Hash {
+        Sha256::digest(msg).to_vec().into()
+    }
+}
+
+/// A memory wal storage which records whether it is synced.
+#[derive(Clone, Default)]
+struct SyncedStorage {
+    storage: MemoryWalStorage,
+    synced: Arc<AtomicBool>,
+}
+
+impl WalStorage for SyncedStorage {
+    fn current_height(&mut self) -> io::Result<Height> {
+        self.storage.current_height()
+    }
+
+    fn set_current_height(&mut self, height: Height) -> io::Result<()> {
+        self.storage.set_current_height(height)
+    }
+
+    fn append(&mut self, height: Height, data: &[u8]) -> io::Result<()> {
+        self.synced.store(false, Ordering::SeqCst);
+        self.storage.append(height, data)
+    }
+
+    fn sync(&mut self) -> io::Result<()> {
+        self.synced.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    fn load(&mut self, height: Height) -> io::Result<Vec<u8>> {
+        self.storage.load(height)
+    }
+
+    fn set_len(&mut self, height: Height, len: u64) -> io::Result<()> {
+        self.storage.set_len(height, len)
+    }
+
+    fn truncate_below(&mut self, height: Height) -> io::Result<()> {
+        self.storage.truncate_below(height)
+    }
+
+    fn clear(&mut self) -> io::Result<()> {
+        self.storage.clear()
+    }
+}
+
+fn no_sync_config() -> BftConfig {
+    BftConfig::builder()
+        .wal_config(WalConfig {
+            durability: WalDurability::NoSync,
+            ..WalConfig::default()
+        })
+        .build()
+        .unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_async_consensus() {
+    run_consensus().await;
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn test_async_consensus_current_thread() {
+    run_consensus().await;
+}
+
+#[test]
+fn test_async_without_runtime() {
+    let (msg_send, _) = unbounded_channel();
+    let (commit_send, _) = unbounded_channel();
+    let address: Address = vec![0].into();
+    let support = Support {
+        address: address.clone(),
+        authority_list: vec![Node::set_address(address.clone())],
+        msg_send,
+        commit_send,
+    };
+    let result = AsyncBftActuator::new(
+        Arc::new(support),
+        address,
+        MemoryWalStorage::new(),
+        no_sync_config(),
+    );
+    assert!(matches!(result, Err(BftError::SpawnThreadErr(_))));
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn test_async_kill() {
+    let (msg_send, _msg_recv) = unbounded_channel();
+    let (commit_send, _commit_recv) = unbounded_channel();
+    let address: Address = vec![0].into();
+    // node 1 is the proposer and never proposes, so node 0 signs nothing
+    let authority_list = vec![
+        Node::set_address(address.clone()),
+        Node::set_address(vec![1].into()),
+    ];
+    let support = Support {
+        address: address.clone(),
+        authority_list: authority_list.clone(),
+        msg_send,
+        commit_send,
+    };
+    let mut storage = SyncedStorage::default();
+    let config = BftConfig::builder()
+        .wal_config(WalConfig {
+            durability: WalDurability::GroupCommit(60_000),
+            ..WalConfig::default()
+        })
+        .build()
+        .unwrap();
+    let actuator =
+        AsyncBftActuator::new(Arc::new(support), address, storage.clone(), config).unwrap();
+    actuator.send(BftMsg::Start).unwrap();
+    actuator
+        .send(BftMsg::Status(Status {
+            height: 0,
+            interval: Some(INTERVAL),
+            authority_list,
+        }))
+        .unwrap();
+    // the status is saved, which is not synced until the group commit
+    while storage.load(1).unwrap().is_empty() {
+        sleep(Duration::from_millis(10)).await;
+    }
+    assert!(!storage.synced.load(Ordering::SeqCst));
+
+    actuator.send(BftMsg::Kill).unwrap();
+    timeout(Duration::from_secs(10), async {
+        while !storage.synced.load(Ordering::SeqCst) {
+            sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("the wal log is not synced when the actuator is killed");
+}
+
+async fn run_consensus() {
+    let (msg_send, mut msg_recv) = unbounded_channel();
+    let (commit_send, mut commit_recv) = unbounded_channel();
+    let addresses: Vec<Address> = (0..NODE_NUM).map(|i| vec![i as u8].into()).collect();
+    let authority_list: Vec<Node> = addresses.iter().cloned().map(Node::set_address).collect();
+
+    let mut actuators = HashMap::new();
+    for address in addresses.iter() {
+        let support = Support {
+            address: address.clone(),
+            authority_list: authority_list.clone(),
+            msg_send: msg_send.clone(),
+            commit_send: commit_send.clone(),
+        };
+        let actuator = AsyncBftActuator::new(
+            Arc::new(support),
+            address.clone(),
+            MemoryWalStorage::new(),
+            no_sync_config(),
+        )
+        .unwrap();
+        actuator.send(BftMsg::Start).unwrap();
+        actuator
+            .send(BftMsg::Status(Status {
+                height: 0,
+                interval: Some(INTERVAL),
+                authority_list: authority_list.clone(),
+            }))
+            .unwrap();
+        actuators.insert(address.clone(), actuator);
+    }
+
+    let mut commits: HashMap<Height, Vec<Commit>> = HashMap::new();
+    let run = async {
+        loop {
+            tokio::select! {
+                Some((msg, from)) = msg_recv.recv() => {
+                    for (address, actuator) in actuators.iter() {
+                        if *address != from {
+                            actuator.send(msg.clone()).unwrap();
+                        }
+                    }
+                }
+                Some((commit, _)) = commit_recv.recv() => {
+                    let height_commits = commits.entry(commit.height).or_default();
+                    height_commits.push(commit);
+                    if commits.get(&3).map_or(0, |c| c.len()) == NODE_NUM {
+                        break;
+                    }
+                }
+            }
+        }
+    };
+    timeout(Duration::from_secs(60), run)
+        .await
+        .expect("the async actuators do not reach height 3 in time");
+
+    for height in 1..=3 {
+        let height_commits = &commits[&height];
+        assert!(height_commits
+            .iter()
+            .all(|commit| commit.block == height_commits[0].block));
+    }
+}
@@ -1,12 +1,10 @@
 use super::utils::RandomMode;
 use std::time::Duration;
-use std::u64::MAX as MAX_U64;
-use std::u8::MAX as MAX_U8;
 
 pub const LIVENESS_TICK: Duration = Duration::from_secs(60);
 pub const ADDRESS_SIZE: usize = 20; // 160
-pub const RANDOM_U8: RandomMode = RandomMode::Uniform(0u64, MAX_U8 as u64);
-pub const RANDOM_U64: RandomMode = RandomMode::Uniform(0u64, MAX_U64);
+pub const RANDOM_U8: RandomMode = RandomMode::Uniform(0u64, u8::MAX as u64);
+pub const RANDOM_U64: RandomMode = RandomMode::Uniform(0u64, u64::MAX);
 
 #[derive(Clone, Copy)]
 pub struct Config {
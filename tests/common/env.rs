@@ -1,11 +1,12 @@
 extern crate bft_rs;
 
 use self::bft_rs::timer::{GetInstant, WaitTimer};
+use self::bft_rs::wal::MemoryWalStorage;
 use self::bft_rs::{Address, Hash, Height};
 use super::config::{Config, LIVENESS_TICK};
 use super::support::Support;
 use super::utils::*;
-use bft_rs::{BftActuator, BftMsg, Commit, Node, Status};
+use bft_rs::{check_evidence, BftActuator, BftMsg, Commit, Node, Status, WalConfig, WalDurability};
 use crossbeam::crossbeam_channel::{select, unbounded, Receiver, RecvError, Sender};
 #[allow(unused_imports)]
 use log::{info, log};
@@ -16,9 +17,14 @@ use std::sync::Arc;
 use std::thread;
 use std::time::{Duration, Instant};
 
+pub enum WalBackend {
+    Dir(&'static str),
+    Memory(Vec<MemoryWalStorage>),
+}
+
 pub struct Env {
     pub config: Config,
-    pub wal_dir: &'static str,
+    pub wal: WalBackend,
     pub live_nodes: HashMap<Address, Box<BftActuator>>,
     pub byzantine_nodes: Vec<Address>,
     pub msg_recv: Receiver<(BftMsg, Address)>,
@@ -39,6 +45,15 @@ pub struct Env {
 
 impl Env {
     pub fn new(config: Config, nodes_num: usize, wal_dir: &'static str) -> Env {
+        Env::with_wal(config, nodes_num, WalBackend::Dir(wal_dir))
+    }
+
+    pub fn new_with_memory_wal(config: Config, nodes_num: usize) -> Env {
+        let storages = (0..nodes_num).map(|_| MemoryWalStorage::new()).collect();
+        Env::with_wal(config, nodes_num, WalBackend::Memory(storages))
+    }
+
+    fn with_wal(config: Config, nodes_num: usize, wal: WalBackend) -> Env {
         let mut live_nodes = HashMap::new();
         let mut nodes_height = HashMap::new();
         let mut authority_list = vec![];
@@ -60,8 +75,7 @@ impl Env {
                 msg_send: msg_send.clone(),
                 commit_send: commit_send.clone(),
             };
-            let wal_path = format!("{}{}", wal_dir, i);
-            let actuator = BftActuator::new(Arc::new(node_support), address.clone(), &wal_path);
+            let actuator = build_actuator(&wal, node_support, address.clone(), i);
             live_nodes.insert(address.clone(), Box::new(actuator));
             nodes_height.insert(address, 0);
         }
@@ -89,7 +103,7 @@ impl Env {
 
         Env {
             config,
-            wal_dir,
+            wal,
             live_nodes,
             byzantine_nodes: vec![],
             msg_recv,
@@ -141,6 +155,7 @@ impl Env {
                 });
             }
             if let Ok((commit, sender)) = get_commit {
+                self.check_evidences(&commit);
                 let ch = commit.height;
                 let sh = self.status.height;
                 if ch < sh {
@@ -168,8 +183,8 @@ impl Env {
                 } else if ch == sh + 1 {
                     if ch == stop_height {
                         self.live_nodes
-                            .iter()
-                            .for_each(|(_, actuator)| actuator.send(BftMsg::Kill).unwrap());
+                            .values_mut()
+                            .for_each(|actuator| actuator.shutdown().unwrap());
                         break;
                     }
                     info!(
@@ -242,8 +257,8 @@ impl Env {
                         self.live_nodes.insert(to, Box::new(actuator));
                     }
                     Content::Stop => {
-                        let actuator = self.live_nodes.remove(&to).unwrap();
-                        actuator.send(BftMsg::Kill).unwrap();
+                        let mut actuator = self.live_nodes.remove(&to).unwrap();
+                        actuator.shutdown().unwrap();
                         info!("Node {:?} is stopped", to);
                     }
                 }
@@ -260,8 +275,7 @@ impl Env {
             msg_send: self.msg_send.clone(),
             commit_send: self.commit_send.clone(),
         };
-        let wal_path = format!("{}{}", self.wal_dir, i);
-        BftActuator::new(Arc::new(node_support), address, &wal_path)
+        build_actuator(&self.wal, node_support, address, i)
     }
 
     pub fn check_consistency(&mut self, commit: &Commit) {
@@ -276,6 +290,21 @@ impl Env {
         }
     }
 
+    pub fn check_evidences(&self, commit: &Commit) {
+        commit.evidences.iter().for_each(|evidence| {
+            if !check_evidence(evidence, hash, |sig, _| Some(sig.to_vec().into())) {
+                panic!("invalid evidence {:?} in commit {:?}", evidence, commit);
+            }
+            if !self.byzantine_nodes.contains(&evidence.address) {
+                panic!(
+                    "honest node equivocates {:?} in commit {:?}",
+                    evidence, commit
+                );
+            }
+            info!("commit {:?} contains {:?}", commit, evidence);
+        });
+    }
+
     pub fn create_status(&mut self, height: u64) -> Status {
         let status = Status {
             height,
@@ -329,9 +358,7 @@ impl Env {
     }
 
     pub fn get_node_address(&self, i: usize) -> Option<Address> {
-        self.authority_list
-            .get(i)
-            .and_then(|node| Some(node.address.clone()))
+        self.authority_list.get(i).map(|node| node.address.clone())
     }
 
     pub fn set_node(&mut self, i: usize, content: Content, duration: Duration) {
@@ -362,7 +389,7 @@ impl Eq for Event {}
 
 impl PartialOrd for Event {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        self.process_time.partial_cmp(&other.process_time)
+        Some(self.cmp(other))
     }
 }
 
@@ -388,3 +415,22 @@ pub enum Content {
     Start(usize),
     Corrupt,
 }
+
+fn build_actuator(wal: &WalBackend, support: Support, address: Address, i: usize) -> BftActuator {
+    match wal {
+        WalBackend::Dir(wal_dir) => {
+            let wal_path = format!("{}{}", wal_dir, i);
+            BftActuator::new(Arc::new(support), address, &wal_path).unwrap()
+        }
+        WalBackend::Memory(storages) => BftActuator::with_wal_storage(
+            Arc::new(support),
+            address,
+            storages[i].clone(),
+            WalConfig {
+                durability: WalDurability::NoSync,
+                ..WalConfig::default()
+            },
+        )
+        .unwrap(),
+    }
+}
@@ -31,8 +31,7 @@ impl BftSupport for Support {
             Ok(VerifyResp {
                 is_pass: true,
                 round: height_round.1,
-                #[cfg(feature = "compact_block")]
-                complete_block: get_complete_block(block),
+                complete_block: Some(get_complete_block(block)),
             })
         } else {
             Err(TestError::CheckTxsFailed)
@@ -1,7 +1,6 @@
 use rand::distributions::{Distribution, Normal, Uniform};
 use std::fs::{self, read_dir};
 use std::time::Duration;
-use std::u64::MAX as MAX_U64;
 
 use super::config::*;
 use bft_rs::*;
@@ -26,10 +25,7 @@ pub fn generate_block(byzantine: bool, config: &Config) -> Block {
     } else {
         config.max_block_size
     };
-    let mut vec = Vec::with_capacity(size);
-    unsafe {
-        vec.set_len(size);
-    }
+    let mut vec = vec![0u8; size];
     let mark = if byzantine { 1u8 } else { 0u8 };
     vec.insert(0, mark);
     for i in 1..config.min_block_size {
@@ -40,10 +36,7 @@ pub fn generate_block(byzantine: bool, config: &Config) -> Block {
 
 pub fn get_complete_block(block: &Block) -> Block {
     let complete_block_len = block.as_slice().len() * 2;
-    let mut vec = Vec::with_capacity(complete_block_len);
-    unsafe {
-        vec.set_len(complete_block_len);
-    }
+    let mut vec = vec![0u8; complete_block_len];
     vec.extend_from_slice(block);
     vec.extend_from_slice(block);
     vec.into()
@@ -154,7 +147,7 @@ pub fn set_log_file(path: &str, level: LevelFilter) {
 
 pub fn get_dice_result(likelihood: f64) -> bool {
     let rand_num = get_random_integer(RANDOM_U64) as f64;
-    let rate = rand_num / ((MAX_U64 - 1) as f64);
+    let rate = rand_num / ((u64::MAX - 1) as f64);
     rate > likelihood
 }
 
@@ -165,31 +158,23 @@ pub enum RandomMode {
 }
 
 pub fn get_random_integer(mode: RandomMode) -> u64 {
-    let v;
     match mode {
-        RandomMode::Normal(_, _) => {
-            v = get_random_float(mode) as u64;
-        }
+        RandomMode::Normal(_, _) => get_random_float(mode) as u64,
         RandomMode::Uniform(lower_bound, upper_bound) => {
             let between = Uniform::from(lower_bound..upper_bound);
-            v = between.sample(&mut rand::thread_rng());
+            between.sample(&mut rand::thread_rng())
         }
     }
-    v
 }
 
 pub fn get_random_float(mode: RandomMode) -> f64 {
-    let v;
     match mode {
         RandomMode::Normal(mean, standard_deviation) => {
             let normal = Normal::new(mean, standard_deviation);
-            v = normal.sample(&mut rand::thread_rng());
-        }
-        RandomMode::Uniform(_, _) => {
-            v = get_random_integer(mode) as f64;
+            normal.sample(&mut rand::thread_rng())
         }
+        RandomMode::Uniform(_, _) => get_random_integer(mode) as f64,
     }
-    v
 }
 
 fn hash_slice<T, H>(slice: &[T], digest: &mut H)
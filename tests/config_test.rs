@@ -0,0 +1,100 @@
+use bft_rs::error::BftError;
+use bft_rs::params::{BftConfig, Quorum, RoundBackoff};
+use bft_rs::{WalConfig, WalDurability};
+
+fn is_invalid(result: Result<BftConfig, BftError>) -> bool {
+    matches!(result, Err(BftError::InvalidConfig(_)))
+}
+
+#[test]
+fn test_config_default() {
+    assert_eq!(BftConfig::builder().build(), Ok(BftConfig::default()));
+}
+
+#[test]
+fn test_config_build() {
+    let wal = WalConfig {
+        durability: WalDurability::EveryRecord,
+        retention: 5,
+    };
+    let config = BftConfig::builder()
+        .propose_fraction(2, 3)
+        .prevote_fraction(1, 6)
+        .precommit_fraction(1, 6)
+        .round_backoff(RoundBackoff::Linear)
+        .backoff_limit(0)
+        .reputation_window(3)
+        .commit_quorum(Quorum::new(3, 4).unwrap())
+        .lock_quorum(Quorum::new(2, 3).unwrap())
+        .retransmit_coef(3)
+        .verify_await_coef(10)
+        .cache_size(8)
+        .low_height_message_coef(5)
+        .wal_config(wal)
+        .build()
+        .unwrap();
+    assert_ne!(config, BftConfig::default());
+}
+
+#[test]
+fn test_config_validation() {
+    assert!(is_invalid(
+        BftConfig::builder().propose_fraction(1, 0).build()
+    ));
+    assert!(is_invalid(
+        BftConfig::builder().prevote_fraction(0, 30).build()
+    ));
+    assert!(is_invalid(
+        BftConfig::builder().precommit_fraction(31, 30).build()
+    ));
+    // 28/30 + 1/30 + 2/30 > 1
+    assert!(is_invalid(
+        BftConfig::builder()
+            .propose_fraction(28, 30)
+            .precommit_fraction(2, 30)
+            .build()
+    ));
+    // the products of the denominators overflow
+    assert!(is_invalid(
+        BftConfig::builder()
+            .propose_fraction(1, u64::MAX)
+            .prevote_fraction(1, u64::MAX)
+            .precommit_fraction(1, u64::MAX)
+            .build()
+    ));
+    assert!(is_invalid(BftConfig::builder().backoff_limit(17).build()));
+    assert!(is_invalid(BftConfig::builder().retransmit_coef(0).build()));
+    assert!(is_invalid(
+        BftConfig::builder().verify_await_coef(0).build()
+    ));
+    assert!(is_invalid(
+        BftConfig::builder().low_height_message_coef(0).build()
+    ));
+    assert!(is_invalid(BftConfig::builder().cache_size(1).build()));
+    let wal = WalConfig {
+        durability: WalDurability::default(),
+        retention: 0,
+    };
+    assert!(is_invalid(BftConfig::builder().wal_config(wal).build()));
+}
+
+#[test]
+fn test_quorum() {
+    // more than 2/3 is the classic rule of n >= 3f + 1
+    for (total, f) in [(4, 1), (5, 1), (6, 1), (7, 2), (10, 3)] {
+        assert!(Quorum::TWO_THIRDS.is_reached(total - f, total));
+        assert!(!Quorum::TWO_THIRDS.is_reached(total - f - 1, total));
+    }
+    let strict = Quorum::new(3, 4).unwrap();
+    assert!(!strict.is_reached(3, 4));
+    assert!(strict.is_reached(4, 4));
+
+    // a quorum below 2/3 is unsafe, and a quorum of 1 can never be exceeded
+    for (numerator, denominator) in [(1, 2), (3, 3), (4, 3), (0, 0), (u64::MAX, u64::MAX)] {
+        assert!(matches!(
+            Quorum::new(numerator, denominator),
+            Err(BftError::InvalidConfig(_))
+        ));
+    }
+    assert_eq!(Quorum::new(2, 3), Ok(Quorum::TWO_THIRDS));
+}
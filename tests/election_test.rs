@@ -0,0 +1,137 @@
+use bft_rs::election::{
+    PriorityElection, ProofElection, ProposerElection, RandomElection, TurnElection,
+};
+use bft_rs::{Address, Hash, Node, Proof};
+
+fn authorities(proposal_weights: &[u32]) -> Vec<Node> {
+    proposal_weights
+        .iter()
+        .enumerate()
+        .map(|(i, weight)| Node::new(Address::from(vec![i as u8]), *weight, 1))
+        .collect()
+}
+
+#[test]
+fn test_turn_election() {
+    let authorities = authorities(&[1, 2, 1]);
+    let proposers: Vec<usize> = (0..8)
+        .map(|height| TurnElection.elect(height, 0, &authorities, None))
+        .collect();
+    assert_eq!(proposers, vec![0, 1, 1, 2, 0, 1, 1, 2]);
+    // the next round is proposed by the proposer of the next height
+    assert_eq!(TurnElection.elect(3, 1, &authorities, None), 0);
+}
+
+#[test]
+fn test_random_election() {
+    let authorities = authorities(&[1, 2, 1]);
+    let mut counts = [0; 3];
+    for height in 0..4000 {
+        let index = RandomElection.elect(height, 0, &authorities, None);
+        assert_eq!(RandomElection.elect(height, 0, &authorities, None), index);
+        counts[index] += 1;
+    }
+    // the proposers are chosen in proportion to the weights
+    assert!(counts[1] > counts[0] * 3 / 2);
+    assert!(counts[1] > counts[2] * 3 / 2);
+}
+
+#[test]
+fn test_proof_election() {
+    let authorities = authorities(&[1, 1, 1, 1]);
+    let proof = |height: u64, block_hash: &[u8]| Proof {
+        height,
+        block_hash: Hash::from(block_hash),
+        ..Proof::default()
+    };
+    let schedule = |proof: &Proof| -> Vec<usize> {
+        (0..16)
+            .map(|round| ProofElection.elect(proof.height + 1, round, &authorities, Some(proof)))
+            .collect()
+    };
+
+    let first = proof(5, b"first block");
+    assert_eq!(schedule(&first), schedule(&first.clone()));
+    // the schedule depends on the committed block and its height
+    assert_ne!(schedule(&first), schedule(&proof(5, b"second block")));
+    assert_ne!(schedule(&first), schedule(&proof(6, b"first block")));
+    // the signatures of the proof, which differ among the nodes, are not used
+    let mut signed = first.clone();
+    signed
+        .precommit_votes
+        .insert(Address::from(vec![0]), vec![1, 2, 3].into());
+    assert_eq!(schedule(&first), schedule(&signed));
+    // the proposer is chosen in turn without the proof
+    assert_eq!(
+        ProofElection.elect(6, 1, &authorities, None),
+        TurnElection.elect(6, 1, &authorities, None)
+    );
+}
+
+/// The states of the election of the heights, folded over the authority lists from the height.
+fn fold_states(
+    election: &dyn ProposerElection,
+    from: u64,
+    lists: &[(&[Node], u64)],
+) -> Vec<Vec<u8>> {
+    let mut states: Vec<Vec<u8>> = Vec::new();
+    let mut height = from;
+    for (authorities, heights) in lists {
+        for _ in 0..*heights {
+            let state = election.get_state(height, authorities, states.last().map(Vec::as_slice));
+            states.push(state);
+            height += 1;
+        }
+    }
+    states
+}
+
+#[test]
+fn test_priority_election() {
+    let authorities = authorities(&[5, 1, 1]);
+    let election = PriorityElection::new();
+    let proposers: Vec<usize> = (0..14)
+        .map(|height| election.elect(height, 0, &authorities, None))
+        .collect();
+    // the proposers are interleaved in proportion to the weights
+    assert_eq!(proposers[..7], [0, 0, 1, 0, 2, 0, 0]);
+    assert_eq!(proposers[..7], proposers[7..]);
+
+    // a round takes a step more
+    assert_eq!(election.elect(1, 1, &authorities, None), 1);
+    assert_eq!(election.elect(1, 3, &authorities, None), 2);
+
+    // the carried priorities are the ones rebuilt from the height
+    for (weights, from) in [(&[5, 1, 1][..], 9), (&[3, 2, 4, 1], 57), (&[1, 1, 1, 1], 6)] {
+        let authorities = self::authorities(weights);
+        let states = fold_states(&election, from, &[(&authorities, 30)]);
+        for (height, state) in (from..).zip(states.iter()) {
+            assert_eq!(
+                election.elect_by_state(height, 0, &authorities, None, state),
+                election.elect(height, 0, &authorities, None)
+            );
+            assert_eq!(state, &election.get_state(height, &authorities, None));
+        }
+    }
+}
+
+#[test]
+fn test_priority_election_authority_change() {
+    let election = PriorityElection::new();
+    let old = authorities(&[1, 1, 1]);
+    // node 3 joins at height 5, the priorities are rebuilt at height 8 of a multiple of 4
+    let new = authorities(&[1, 1, 1, 1]);
+    let states = fold_states(&election, 1, &[(&old, 4), (&new, 8)]);
+    let proposers: Vec<usize> = (5..13)
+        .zip(&states[4..])
+        .map(|(height, state)| election.elect_by_state(height, 0, &new, None, state))
+        .collect();
+    // node 3 joins with a low priority, so the others propose first
+    assert!(!proposers[..3].contains(&3));
+    let rebuilt: Vec<usize> = (5..13)
+        .map(|height| election.elect(height, 0, &new, None))
+        .collect();
+    assert_ne!(proposers[..3], rebuilt[..3]);
+    // a node without the carried priorities elects as the others from height 8
+    assert_eq!(proposers[3..], rebuilt[3..]);
+}
@@ -0,0 +1,1344 @@
+use bft_rs::clock::{Clock, MockClock};
+use bft_rs::election::{PriorityElection, ProofElection, ProposerElection, TurnElection};
+use bft_rs::engine::{BftAction, BftEngine};
+use bft_rs::objects::Step;
+use bft_rs::params::{BftConfig, Quorum, RoundBackoff};
+use bft_rs::timer::{GetInstant, TimeoutInfo};
+use bft_rs::utils::{combine_two, extract_two};
+use bft_rs::wal::{MemoryWalStorage, WalStorage};
+use bft_rs::*;
+use crossbeam::crossbeam_channel::unbounded;
+use rlp::{Rlp, RlpStream};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, VecDeque};
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const NODE_NUM: usize = 4;
+const INTERVAL: u64 = 3000;
+const COMPLETE_MARK: &[u8] = b" with complete txs";
+
+#[derive(Debug)]
+struct TestError;
+
+/// A support whose signature is its address, and which records the reported misbehaviors.
+struct Support {
+    address: Address,
+    misbehaviors: Arc<Mutex<Vec<Misbehavior>>>,
+}
+
+impl BftSupport for Support {
+    type Error = TestError;
+
+    fn check_block(
+        &self,
+        block: &Block,
+        _block_hash: &Hash,
+        _signed_proposal_hash: &Hash,
+        height_round: (Height, Round),
+        _is_lock: bool,
+        _proposer: &Address,
+    ) -> Result<VerifyResp, TestError> {
+        Ok(VerifyResp {
+            is_pass: !block.is_empty(),
+            round: height_round.1,
+            complete_block: Some(block.clone()),
+        })
+    }
+
+    fn transmit(&self, _msg: BftMsg) {
+        unreachable!("the engine returns broadcasts as actions");
+    }
+
+    fn commit(&self, _commit: Commit) -> Result<Status, TestError> {
+        unreachable!("the engine returns commits as actions");
+    }
+
+    fn get_block(&self, _height: Height) -> Result<(Block, Hash), TestError> {
+        unreachable!("the engine returns block requests as actions");
+    }
+
+    fn sign(&self, _hash: &Hash) -> Result<Signature, TestError> {
+        Ok(self.address.to_vec().into())
+    }
+
+    fn check_sig(&self, signature: &Signature, _hash: &Hash) -> Result<Address, TestError> {
+        Ok(signature.to_vec().into())
+    }
+
+    fn crypt_hash(&self, msg: &[u8]) -> Hash {
+        Sha256::digest(msg).to_vec().into()
+    }
+
+    fn report_misbehavior(&self, misbehavior: Misbehavior) {
+        self.misbehaviors.lock().unwrap().push(misbehavior);
+    }
+}
+
+/// Encode a vote signed by the signer the same as in BftMsg::Vote.
+fn signed_vote(
+    vote_type: u8,
+    height: Height,
+    round: Round,
+    block_hash: &Hash,
+    voter: &Address,
+    signer: &Address,
+) -> Vec<u8> {
+    let mut vote = RlpStream::new_list(5);
+    vote.append(&vote_type)
+        .append(&height)
+        .append(&round)
+        .append(block_hash)
+        .append(voter);
+    let mut signed_vote = RlpStream::new_list(2);
+    signed_vote
+        .append_raw(&vote.out(), 1)
+        .append(&Signature::from(signer.to_vec()));
+    signed_vote.out()
+}
+
+/// Encode a proposal signed by the proposer with its block the same as in BftMsg::Proposal,
+/// the lock votes are encoded signed votes.
+fn signed_proposal(
+    height: Height,
+    round: Round,
+    block: &[u8],
+    lock_round: Option<Round>,
+    lock_votes: &[Vec<u8>],
+    proposer: &Address,
+) -> Vec<u8> {
+    let block_hash: Hash = Sha256::digest(block).to_vec().into();
+    let mut proposal = RlpStream::new_list(7);
+    proposal
+        .append(&height)
+        .append(&round)
+        .append(&block_hash)
+        .append(&Proof::default())
+        .append(&lock_round)
+        .begin_list(lock_votes.len());
+    for lock_vote in lock_votes {
+        proposal.append_raw(lock_vote, 1);
+    }
+    proposal.append(proposer);
+    let mut signed_proposal = RlpStream::new_list(2);
+    signed_proposal
+        .append_raw(&proposal.out(), 1)
+        .append(&Signature::from(proposer.to_vec()));
+    combine_two(&signed_proposal.out(), block)
+}
+
+/// Frame a wal record the same as the wal log, whose header is
+/// [len u32][type u8][crc32 u32][header crc32 u32].
+fn wal_record(log_type: u8, msg: &[u8]) -> Vec<u8> {
+    let len = (msg.len() as u32).to_le_bytes();
+    let mut crc = crc32fast::Hasher::new();
+    crc.update(&len);
+    crc.update(&[log_type]);
+    crc.update(msg);
+    let mut record = len.to_vec();
+    record.push(log_type);
+    record.extend_from_slice(&crc.finalize().to_le_bytes());
+    let mut header_crc = crc32fast::Hasher::new();
+    header_crc.update(&record);
+    record.extend_from_slice(&header_crc.finalize().to_le_bytes());
+    record.extend_from_slice(msg);
+    record
+}
+
+/// The config of the tests, which does not depend on the features.
+fn test_config(durability: WalDurability) -> BftConfig {
+    BftConfig::builder()
+        .verify_req(false)
+        .machine_gun(false)
+        .random_proposer(false)
+        .compact_block(false)
+        .wal_config(WalConfig {
+            durability,
+            ..WalConfig::default()
+        })
+        .build()
+        .unwrap()
+}
+
+/// A memory wal storage which records the length of each log when it is synced.
+#[derive(Clone, Default)]
+struct SyncedStorage {
+    storage: MemoryWalStorage,
+    synced: Arc<Mutex<BTreeMap<Height, usize>>>,
+}
+
+impl SyncedStorage {
+    /// The number of bytes appended but not synced.
+    fn unsynced(&self) -> usize {
+        let mut storage = self.storage.clone();
+        self.synced
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(height, len)| storage.load(*height).unwrap().len() - len)
+            .sum()
+    }
+
+    /// Whether the data is in the synced part of a log.
+    fn is_synced(&self, data: &[u8]) -> bool {
+        let mut storage = self.storage.clone();
+        self.synced.lock().unwrap().iter().any(|(height, len)| {
+            storage.load(*height).unwrap()[..*len]
+                .windows(data.len())
+                .any(|window| window == data)
+        })
+    }
+}
+
+impl WalStorage for SyncedStorage {
+    fn current_height(&mut self) -> io::Result<Height> {
+        self.storage.current_height()
+    }
+
+    fn set_current_height(&mut self, height: Height) -> io::Result<()> {
+        self.storage.set_current_height(height)
+    }
+
+    fn append(&mut self, height: Height, data: &[u8]) -> io::Result<()> {
+        self.synced.lock().unwrap().entry(height).or_insert(0);
+        self.storage.append(height, data)
+    }
+
+    fn sync(&mut self) -> io::Result<()> {
+        for (height, len) in self.synced.lock().unwrap().iter_mut() {
+            *len = self.storage.load(*height)?.len();
+        }
+        Ok(())
+    }
+
+    fn load(&mut self, height: Height) -> io::Result<Vec<u8>> {
+        self.storage.load(height)
+    }
+
+    fn set_len(&mut self, height: Height, len: u64) -> io::Result<()> {
+        if let Some(synced) = self.synced.lock().unwrap().get_mut(&height) {
+            *synced = (*synced).min(len as usize);
+        }
+        self.storage.set_len(height, len)
+    }
+
+    fn truncate_below(&mut self, height: Height) -> io::Result<()> {
+        let mut synced = self.synced.lock().unwrap();
+        *synced = synced.split_off(&height);
+        self.storage.truncate_below(height)
+    }
+
+    fn clear(&mut self) -> io::Result<()> {
+        self.synced.lock().unwrap().clear();
+        self.storage.clear()
+    }
+}
+
+/// A network of step-driven engines, which runs in the virtual time of a mock clock.
+struct Network {
+    clock: MockClock,
+    config: BftConfig,
+    election: Option<Arc<dyn ProposerElection>>,
+    authority_list: Vec<Node>,
+    engines: Vec<BftEngine<Support>>,
+    storages: Vec<SyncedStorage>,
+    misbehaviors: Vec<Arc<Mutex<Vec<Misbehavior>>>>,
+    inbox: VecDeque<(usize, BftMsg)>,
+    timers: Vec<(usize, TimeoutInfo)>,
+    commits: Vec<Vec<Commit>>,
+    broadcasts: Vec<(usize, BftMsg)>,
+    // a node which is down, so its msgs are dropped
+    down: Option<usize>,
+    // check that the own proposals and votes are synced before they are broadcast
+    check_synced: bool,
+}
+
+impl Network {
+    fn new() -> Self {
+        Network::with_config(test_config(WalDurability::NoSync))
+    }
+
+    fn with_config(config: BftConfig) -> Self {
+        Network::with_election(config, None, None, &[1; NODE_NUM])
+    }
+
+    /// A network with the proposal weights, whose engines use the election if it is given.
+    fn with_election(
+        config: BftConfig,
+        election: Option<Arc<dyn ProposerElection>>,
+        down: Option<usize>,
+        proposal_weights: &[u32],
+    ) -> Self {
+        let authority_list = (0..NODE_NUM)
+            .zip(proposal_weights)
+            .map(|(i, weight)| Node::new(vec![i as u8].into(), *weight, 1))
+            .collect();
+        let mut network = Network {
+            clock: MockClock::new(),
+            config,
+            election,
+            authority_list,
+            engines: Vec::new(),
+            storages: vec![SyncedStorage::default(); NODE_NUM],
+            misbehaviors: (0..NODE_NUM).map(|_| Arc::default()).collect(),
+            inbox: VecDeque::new(),
+            timers: Vec::new(),
+            commits: vec![Vec::new(); NODE_NUM],
+            broadcasts: Vec::new(),
+            down,
+            check_synced: false,
+        };
+        network.engines = (0..NODE_NUM).map(|i| network.new_engine(i)).collect();
+        for i in (0..NODE_NUM).filter(|i| Some(*i) != down) {
+            network.inbox.push_back((i, BftMsg::Start));
+            let status = network.status(0);
+            network.inbox.push_back((i, BftMsg::Status(status)));
+        }
+        network
+    }
+
+    /// Create the engine of the node, which replays its wal log.
+    fn new_engine(&self, i: usize) -> BftEngine<Support> {
+        let address: Address = vec![i as u8].into();
+        let support = Arc::new(Support {
+            address: address.clone(),
+            misbehaviors: Arc::clone(&self.misbehaviors[i]),
+        });
+        let storage = self.storages[i].clone();
+        let clock: Arc<dyn Clock> = Arc::new(self.clock.clone());
+        match self.election.clone() {
+            Some(election) => {
+                BftEngine::with_election(support, address, storage, self.config, clock, election)
+            }
+            None => BftEngine::new(support, address, storage, self.config, clock),
+        }
+        .unwrap()
+    }
+
+    /// Restart the node, whose timers are dropped and which replays its wal log,
+    /// then it gets the status of its latest commit as it starts.
+    fn restart(&mut self, i: usize) {
+        self.timers.retain(|(j, _)| *j != i);
+        self.engines[i] = self.new_engine(i);
+        let actions = self.engines[i].take_actions();
+        self.execute(i, actions);
+        self.inbox.push_back((i, BftMsg::Start));
+        let status = self.status(self.commits[i].len() as Height);
+        self.inbox.push_back((i, BftMsg::Status(status)));
+    }
+
+    /// The encoded votes of the type, height and round broadcast by the node.
+    fn broadcast_votes(
+        &self,
+        i: usize,
+        vote_type: u8,
+        height: Height,
+        round: Round,
+    ) -> Vec<Vec<u8>> {
+        self.broadcasts
+            .iter()
+            .filter_map(|(j, msg)| match msg {
+                BftMsg::Vote(encode) if *j == i => Some(encode),
+                _ => None,
+            })
+            .filter(|encode| {
+                let vote = Rlp::new(encode).at(0).unwrap();
+                vote.val_at::<u8>(0).unwrap() == vote_type
+                    && vote.val_at::<Height>(1).unwrap() == height
+                    && vote.val_at::<Round>(2).unwrap() == round
+            })
+            .cloned()
+            .collect()
+    }
+
+    fn status(&self, height: Height) -> Status {
+        Status {
+            height,
+            interval: Some(INTERVAL),
+            authority_list: self.authority_list.clone(),
+        }
+    }
+
+    fn execute(&mut self, i: usize, actions: Vec<BftAction>) {
+        for action in actions {
+            match action {
+                BftAction::Broadcast(msg) => {
+                    if let (true, BftMsg::Proposal(encode) | BftMsg::Vote(encode)) =
+                        (self.check_synced, &msg)
+                    {
+                        assert!(self.storages[i].is_synced(encode));
+                    }
+                    self.broadcasts.push((i, msg.clone()));
+                    for j in (0..NODE_NUM).filter(|j| *j != i) {
+                        self.inbox.push_back((j, msg.clone()));
+                    }
+                }
+                BftAction::Commit(commit) => {
+                    let status = self.status(commit.height);
+                    // a commit sent again, which is replayed after a restart
+                    if self.commits[i].len() < commit.height as usize {
+                        self.commits[i].push(commit);
+                    }
+                    self.inbox.push_back((i, BftMsg::Status(status)));
+                }
+                BftAction::SetTimer(timeout) => self.timers.push((i, timeout)),
+                BftAction::GetBlock(height) => {
+                    let block: Block = format!("block {} of node {}", height, i)
+                        .into_bytes()
+                        .into();
+                    let block_hash = Sha256::digest(&block).to_vec().into();
+                    let feed = Feed {
+                        height,
+                        block,
+                        block_hash,
+                    };
+                    self.inbox.push_back((i, BftMsg::Feed(feed)));
+                }
+                BftAction::CheckBlock(req) => {
+                    let mut complete_block = req.block.to_vec();
+                    complete_block.extend_from_slice(COMPLETE_MARK);
+                    let verify_resp = VerifyResp {
+                        is_pass: true,
+                        round: req.round,
+                        complete_block: Some(complete_block.into()),
+                    };
+                    self.inbox.push_back((i, BftMsg::VerifyResp(verify_resp)));
+                }
+            }
+        }
+    }
+
+    /// Deliver the msgs, or fire the earliest timer if there is no msg in flight.
+    fn step(&mut self) {
+        if let Some((i, msg)) = self.inbox.pop_front() {
+            if Some(i) == self.down {
+                return;
+            }
+            let actions = self.engines[i].handle_msg(msg);
+            self.execute(i, actions);
+            return;
+        }
+        let index = (0..self.timers.len())
+            .min_by_key(|index| self.timers[*index].1.get_instant())
+            .expect("the network is stuck without msgs and timers");
+        let (i, timeout) = self.timers.remove(index);
+        let now = self.clock.now();
+        if timeout.get_instant() > now {
+            self.clock.advance(timeout.get_instant() - now);
+        }
+        let actions = self.engines[i].handle_timeout(timeout);
+        self.execute(i, actions);
+    }
+
+    fn run_to(&mut self, height: Height) {
+        while self
+            .commits
+            .iter()
+            .enumerate()
+            .any(|(i, commits)| Some(i) != self.down && commits.len() < height as usize)
+        {
+            self.step();
+        }
+    }
+}
+
+#[test]
+fn test_engine_consensus() {
+    let mut network = Network::new();
+    network.run_to(5);
+
+    for commits in network.commits.iter() {
+        for (height, commit) in commits.iter().enumerate() {
+            assert_eq!(commit.height, height as Height + 1);
+            assert_eq!(commit.block, network.commits[0][height].block);
+            // every height reaches consensus in the first round in a perfect network
+            assert_eq!(commit.proof.round, 0);
+            assert!(commit.proof.precommit_votes.len() * 3 > NODE_NUM * 2);
+        }
+    }
+}
+
+#[test]
+fn test_engine_config() {
+    let config = BftConfig::builder()
+        .propose_fraction(1, 2)
+        .prevote_fraction(1, 10)
+        .precommit_fraction(1, 10)
+        .cache_size(2)
+        .retransmit_coef(3)
+        .wal_config(WalConfig {
+            durability: WalDurability::NoSync,
+            ..WalConfig::default()
+        })
+        .build()
+        .unwrap();
+    let mut network = Network::with_config(config);
+    network.run_to(3);
+
+    for commits in network.commits.iter() {
+        assert_eq!(commits[2].block, network.commits[0][2].block);
+    }
+}
+
+#[test]
+fn test_engine_large_interval() {
+    // the wait durations of a huge interval do not overflow
+    let network = Network::new();
+    let mut engine = network.new_engine(0);
+    let mut status = network.status(0);
+    status.interval = Some(1 << 60);
+    engine.handle_msg(BftMsg::Start);
+    let mut actions = engine.handle_msg(BftMsg::Status(status));
+    while engine.state().height < 1 {
+        let timeout = actions
+            .into_iter()
+            .find_map(|action| match action {
+                BftAction::SetTimer(timeout) => Some(timeout),
+                _ => None,
+            })
+            .unwrap();
+        actions = engine.handle_timeout(timeout);
+    }
+    assert!(actions
+        .iter()
+        .any(|action| matches!(action, BftAction::SetTimer(_))));
+}
+
+#[test]
+fn test_engine_large_interval_backoff() {
+    // the wait durations of a huge interval saturate instead of overflowing
+    // as they are backed off by the rounds
+    let config = BftConfig::builder()
+        .round_backoff(RoundBackoff::Exponential)
+        .backoff_limit(16)
+        .wal_config(WalConfig {
+            durability: WalDurability::NoSync,
+            ..WalConfig::default()
+        })
+        .build()
+        .unwrap();
+    let network = Network::with_config(config);
+    let mut engine = network.new_engine(0);
+    let mut status = network.status(0);
+    status.interval = Some(u64::MAX);
+    engine.handle_msg(BftMsg::Start);
+    let mut actions = engine.handle_msg(BftMsg::Status(status));
+    for round in 0..20 {
+        assert_eq!(engine.state().round, round);
+        // the other nodes vote nil, and the engine goes to the next round as it times out
+        for vote_type in 0..2 {
+            for i in 1..NODE_NUM {
+                let voter: Address = vec![i as u8].into();
+                let vote = signed_vote(vote_type, 1, round, &Hash::default(), &voter, &voter);
+                actions.extend(engine.handle_msg(BftMsg::Vote(vote)));
+            }
+        }
+        while engine.state().round == round {
+            let timeout = actions
+                .into_iter()
+                .rev()
+                .find_map(|action| match action {
+                    BftAction::SetTimer(timeout) => Some(timeout),
+                    _ => None,
+                })
+                .unwrap();
+            actions = engine.handle_timeout(timeout);
+        }
+    }
+}
+
+#[test]
+fn test_engine_modes() {
+    let config = BftConfig::builder()
+        .verify_req(true)
+        .machine_gun(true)
+        .random_proposer(true)
+        .compact_block(true)
+        .wal_config(WalConfig {
+            durability: WalDurability::NoSync,
+            ..WalConfig::default()
+        })
+        .build()
+        .unwrap();
+    let mut network = Network::with_config(config);
+    network.run_to(3);
+
+    for commits in network.commits.iter() {
+        for (height, commit) in commits.iter().enumerate() {
+            assert_eq!(commit.block, network.commits[0][height].block);
+            // the complete block of the verification is committed instead of the proposed one
+            assert!(commit.block.ends_with(COMPLETE_MARK));
+        }
+    }
+    // the next height starts at once without waiting for the interval
+    assert!(network.clock.elapsed() < Duration::from_millis(INTERVAL * 3));
+}
+
+#[test]
+fn test_verify_resp_encoding() {
+    let verify_resp = VerifyResp {
+        is_pass: true,
+        round: 2,
+        complete_block: None,
+    };
+    let encode = rlp::encode(&verify_resp);
+    assert_eq!(rlp::decode::<VerifyResp>(&encode), Ok(verify_resp));
+
+    let verify_resp = VerifyResp {
+        is_pass: false,
+        round: 3,
+        complete_block: Some(vec![1, 2, 3].into()),
+    };
+    let encode = rlp::encode(&verify_resp);
+    assert_eq!(rlp::decode::<VerifyResp>(&encode), Ok(verify_resp));
+}
+
+#[test]
+fn test_proof_encoding() {
+    let mut proof = Proof {
+        height: 3,
+        round: 1,
+        block_hash: vec![3].into(),
+        ..Proof::default()
+    };
+    proof.precommit_votes.insert(vec![0].into(), vec![4].into());
+    let encode = rlp::encode(&proof);
+    assert_eq!(Rlp::new(&encode).item_count(), Ok(5));
+    assert_eq!(rlp::decode::<Proof>(&encode), Ok(proof));
+}
+
+#[test]
+fn test_engine_round_backoff() {
+    // node 1 is down, and it is the proposer of the first three rounds of height 1
+    let elapsed = |round_backoff, backoff_limit| {
+        let config = BftConfig::builder()
+            .round_backoff(round_backoff)
+            .backoff_limit(backoff_limit)
+            .wal_config(WalConfig {
+                durability: WalDurability::NoSync,
+                ..WalConfig::default()
+            })
+            .build()
+            .unwrap();
+        let mut network =
+            Network::with_election(config, Some(Arc::new(TurnElection)), Some(1), &[1, 3, 1, 1]);
+        network.run_to(1);
+        for commits in network.commits.iter().filter(|commits| !commits.is_empty()) {
+            assert_eq!(commits[0].proof.round, 3);
+        }
+        network.clock.elapsed()
+    };
+
+    let exponential = elapsed(RoundBackoff::Exponential, 4);
+    let linear = elapsed(RoundBackoff::Linear, 4);
+    let constant = elapsed(RoundBackoff::Exponential, 0);
+    // the propose wait of round 2 is 4 times of round 0 by exponential backoff,
+    // and 3 times by linear backoff
+    assert!(exponential > linear);
+    assert!(linear > constant);
+    assert_eq!(elapsed(RoundBackoff::Linear, 0), constant);
+}
+
+/// An election in which the first authority always proposes.
+#[derive(Debug)]
+struct FirstElection;
+
+impl ProposerElection for FirstElection {
+    fn elect(
+        &self,
+        _height: Height,
+        _round: Round,
+        _authorities: &[Node],
+        _proof: Option<&Proof>,
+    ) -> usize {
+        0
+    }
+}
+
+#[test]
+fn test_engine_election() {
+    let config = BftConfig::builder()
+        .wal_config(WalConfig {
+            durability: WalDurability::NoSync,
+            ..WalConfig::default()
+        })
+        .build()
+        .unwrap();
+    let mut network =
+        Network::with_election(config, Some(Arc::new(FirstElection)), None, &[1; NODE_NUM]);
+    network.run_to(3);
+
+    for commits in network.commits.iter() {
+        for (height, commit) in commits.iter().enumerate() {
+            let block = format!("block {} of node 0", height + 1).into_bytes();
+            assert!(commit.block.starts_with(&block));
+        }
+    }
+}
+
+#[test]
+fn test_engine_proof_election() {
+    let config = BftConfig::builder()
+        .wal_config(WalConfig {
+            durability: WalDurability::NoSync,
+            ..WalConfig::default()
+        })
+        .build()
+        .unwrap();
+    let mut network =
+        Network::with_election(config, Some(Arc::new(ProofElection)), None, &[1; NODE_NUM]);
+    network.run_to(5);
+
+    let authority_list = network.authority_list.clone();
+    for commits in network.commits.iter() {
+        let mut proof = Proof::default();
+        for (height, commit) in commits.iter().enumerate() {
+            assert_eq!(commit.block, network.commits[0][height].block);
+            // the proposer is elected by the proof of the previous height
+            let index = ProofElection.elect(
+                commit.height,
+                commit.proof.round,
+                &authority_list,
+                Some(&proof),
+            );
+            assert_eq!(commit.address, authority_list[index].address);
+            proof = commit.proof.clone();
+        }
+    }
+}
+
+#[test]
+fn test_engine_reputation() {
+    // node 1 is down, it is the proposer of round 0 at height 1 and 5 without the reputation
+    let failed_rounds = |reputation_window| {
+        let config = BftConfig::builder()
+            .reputation_window(reputation_window)
+            .wal_config(WalConfig {
+                durability: WalDurability::NoSync,
+                ..WalConfig::default()
+            })
+            .build()
+            .unwrap();
+        let mut network = Network::with_election(
+            config,
+            Some(Arc::new(TurnElection)),
+            Some(1),
+            &[1; NODE_NUM],
+        );
+        network.run_to(8);
+        let commits = &network.commits[0];
+        for commit in commits.iter() {
+            assert_ne!(commit.address, vec![1u8].into());
+        }
+        commits
+            .iter()
+            .take(8)
+            .map(|commit| commit.proof.round)
+            .sum::<Round>()
+    };
+
+    assert_eq!(failed_rounds(0), 2);
+    // node 1 is skipped after it failed at height 1
+    assert_eq!(failed_rounds(10), 1);
+}
+
+#[test]
+fn test_engine_reputation_restart() {
+    let config = BftConfig::builder()
+        .reputation_window(10)
+        .wal_config(WalConfig {
+            durability: WalDurability::NoSync,
+            ..WalConfig::default()
+        })
+        .build()
+        .unwrap();
+    let mut network = Network::with_election(
+        config,
+        Some(Arc::new(TurnElection)),
+        Some(1),
+        &[1; NODE_NUM],
+    );
+    network.run_to(3);
+    // the failed proposer of height 1 is derived from its proof, and reloaded from the wal
+    for i in [0, 2, 3] {
+        network.restart(i);
+    }
+    network.run_to(8);
+
+    for commits in network.commits.iter().filter(|commits| !commits.is_empty()) {
+        for commit in commits.iter().take(8) {
+            assert_ne!(commit.address, vec![1u8].into());
+            assert_eq!(commit.proof.round, if commit.height == 1 { 1 } else { 0 });
+        }
+    }
+}
+
+#[test]
+fn test_engine_priority_election() {
+    let config = BftConfig::builder()
+        .wal_config(WalConfig {
+            durability: WalDurability::NoSync,
+            ..WalConfig::default()
+        })
+        .build()
+        .unwrap();
+    let mut network = Network::with_election(
+        config,
+        Some(Arc::new(PriorityElection::new())),
+        None,
+        &[3, 1, 1, 1],
+    );
+    network.run_to(6);
+
+    for commits in network.commits.iter() {
+        let proposers: Vec<&Address> = commits.iter().take(6).map(|c| &c.address).collect();
+        assert_eq!(
+            proposers,
+            network.commits[0]
+                .iter()
+                .take(6)
+                .map(|c| &c.address)
+                .collect::<Vec<_>>()
+        );
+        // node 0 proposes half of the heights, but not all of them in a row
+        let node_0: Address = vec![0u8].into();
+        assert_eq!(proposers.iter().filter(|p| ***p == node_0).count(), 3);
+        assert!(proposers
+            .windows(3)
+            .all(|w| w.iter().any(|p| **p != node_0)));
+    }
+}
+
+#[test]
+fn test_engine_priority_restart() {
+    let config = BftConfig::builder()
+        .wal_config(WalConfig {
+            durability: WalDurability::NoSync,
+            ..WalConfig::default()
+        })
+        .build()
+        .unwrap();
+    let mut network = Network::with_election(
+        config,
+        Some(Arc::new(PriorityElection::new())),
+        None,
+        &[1; NODE_NUM],
+    );
+    network.run_to(3);
+    // the status of height 4 changes the weights, then the nodes restart after the change
+    let old = network.authority_list.clone();
+    for (node, weight) in network.authority_list.iter_mut().zip(&[3, 1, 2, 1]) {
+        node.proposal_weight = *weight;
+    }
+    let new = network.authority_list.clone();
+    network.run_to(5);
+    for i in 0..NODE_NUM {
+        network.restart(i);
+    }
+    network.run_to(12);
+
+    // the priorities carried from the old authorities are reloaded from the wal,
+    // so the restarted nodes elect as the others until they are rebuilt at height 7
+    let election = PriorityElection::new();
+    let mut state: Option<Vec<u8>> = None;
+    for height in 1..=12 {
+        let authorities = if height <= 4 { &old } else { &new };
+        let next_state = election.get_state(height, authorities, state.as_deref());
+        let index = election.elect_by_state(height, 0, authorities, None, &next_state);
+        if height == 6 {
+            assert_ne!(index, election.elect(height, 0, authorities, None));
+        }
+        for commits in network.commits.iter() {
+            let commit = &commits[height as usize - 1];
+            assert_eq!(commit.proof.round, 0);
+            assert_eq!(commit.address, authorities[index].address);
+        }
+        state = Some(next_state);
+    }
+}
+
+#[test]
+fn test_engine_quorum() {
+    let commit_quorum = Quorum::new(3, 4).unwrap();
+    let config = BftConfig::builder()
+        .commit_quorum(commit_quorum)
+        .wal_config(WalConfig {
+            durability: WalDurability::NoSync,
+            ..WalConfig::default()
+        })
+        .build()
+        .unwrap();
+    let mut network = Network::with_config(config);
+    network.run_to(3);
+
+    let crypt_hash = |msg: &[u8]| -> Hash { Sha256::digest(msg).to_vec().into() };
+    let check_sig = |signature: &Signature, _hash: &Hash| Some(signature.to_vec().into());
+    for commits in network.commits.iter() {
+        for commit in commits.iter() {
+            // more than 3/4 precommits are all of the 4 nodes
+            assert_eq!(commit.proof.precommit_votes.len(), NODE_NUM);
+            assert!(check_proof(
+                &commit.proof,
+                commit.height + 1,
+                &network.authority_list,
+                commit_quorum,
+                crypt_hash,
+                check_sig,
+            ));
+
+            let mut proof = commit.proof.clone();
+            let voter = proof.precommit_votes.keys().next().cloned().unwrap();
+            proof.precommit_votes.remove(&voter);
+            // 3 of the 4 precommits are more than 2/3, but not more than 3/4
+            assert!(!check_proof(
+                &proof,
+                commit.height + 1,
+                &network.authority_list,
+                commit_quorum,
+                crypt_hash,
+                check_sig,
+            ));
+        }
+    }
+}
+
+#[test]
+fn test_engine_state() {
+    let mut network = Network::new();
+    network.run_to(2);
+
+    for engine in network.engines.iter_mut() {
+        let state = engine.state();
+        assert!(state.height >= 2);
+        assert!(state.proof_height >= 1 && state.proof_height <= state.height);
+        assert_eq!(state.authority_list.len(), NODE_NUM);
+
+        let (sender, receiver) = unbounded();
+        assert!(engine.handle_msg(BftMsg::QueryState(sender)).is_empty());
+        assert_eq!(receiver.try_recv(), Ok(state));
+    }
+}
+
+#[test]
+fn test_engine_events() {
+    let mut network = Network::new();
+    let receivers: Vec<_> = network
+        .engines
+        .iter_mut()
+        .map(|engine| engine.subscribe())
+        .collect();
+    network.run_to(2);
+
+    for receiver in receivers {
+        let events: Vec<BftEvent> = receiver.try_iter().collect();
+        let position = |expected: &dyn Fn(&BftEvent) -> bool| {
+            events
+                .iter()
+                .position(expected)
+                .expect("the event is not emitted")
+        };
+        let new_height = position(&|event| *event == BftEvent::NewHeight { height: 1 });
+        let accepted = position(&|event| match event {
+            BftEvent::ProposalAccepted { height, .. } => *height == 1,
+            _ => false,
+        });
+        let prevote_quorum = position(&|event| match event {
+            BftEvent::PrevoteQuorum {
+                height, block_hash, ..
+            } => *height == 1 && block_hash.is_some(),
+            _ => false,
+        });
+        let lock = position(&|event| match event {
+            BftEvent::LockSet { height, .. } => *height == 1,
+            _ => false,
+        });
+        let precommit_quorum = position(&|event| match event {
+            BftEvent::PrecommitQuorum {
+                height, block_hash, ..
+            } => *height == 1 && block_hash.is_some(),
+            _ => false,
+        });
+        let commit = position(&|event| match event {
+            BftEvent::Commit { height, round, .. } => *height == 1 && *round == 0,
+            _ => false,
+        });
+        let timeout = position(&|event| match event {
+            BftEvent::TimeoutFired { height, step, .. } => {
+                *height == 1 && *step == Step::CommitWait
+            }
+            _ => false,
+        });
+        let next_height = position(&|event| *event == BftEvent::NewHeight { height: 2 });
+
+        assert!(new_height < accepted);
+        assert!(accepted < prevote_quorum);
+        assert!(prevote_quorum < lock);
+        assert!(lock < precommit_quorum);
+        assert!(precommit_quorum < commit);
+        assert!(commit < timeout);
+        assert!(timeout < next_height);
+    }
+}
+
+#[test]
+fn test_engine_proposal_evidence() {
+    let mut network = Network::new();
+    // node 1 is the proposer of height 1 round 0, and signs another proposal before its real one
+    let byzantine: Address = vec![1].into();
+    let proposal = signed_proposal(1, 0, b"fake block", None, &[], &byzantine);
+    for i in (0..NODE_NUM).filter(|i| *i != 1) {
+        network
+            .inbox
+            .push_back((i, BftMsg::Proposal(proposal.clone())));
+    }
+    // the others lock the fake block, which node 1 never commits without the fake proposal
+    while (0..NODE_NUM)
+        .filter(|i| *i != 1)
+        .any(|i| network.commits[i].len() < 2)
+    {
+        network.step();
+    }
+    assert!(network.commits[1].is_empty());
+
+    for (i, commits) in network.commits.iter().enumerate() {
+        if i == 1 {
+            continue;
+        }
+        assert_eq!(commits[0].block, b"fake block".to_vec().into());
+        let evidences = &commits[0].evidences;
+        assert_eq!(evidences.len(), 1);
+        let evidence = &evidences[0];
+        assert_eq!(evidence.evidence_type, EvidenceType::DuplicateProposal);
+        assert_eq!((evidence.height, evidence.round), (1, 0));
+        assert_eq!(evidence.address, byzantine);
+        assert!(check_evidence(
+            evidence,
+            |msg| Sha256::digest(msg).to_vec().into(),
+            |signature, _| Some(signature.to_vec().into()),
+        ));
+        assert!(commits[1].evidences.is_empty());
+    }
+}
+
+#[test]
+fn test_engine_conflicting_proposal_commit() {
+    let mut network = Network::new();
+    // node 0 gets a fake proposal of node 1 first, while the others lock its real one
+    let byzantine: Address = vec![1].into();
+    let proposal = signed_proposal(1, 0, b"fake block", None, &[], &byzantine);
+    network.inbox.push_back((0, BftMsg::Proposal(proposal)));
+    network.run_to(2);
+
+    for commits in network.commits.iter() {
+        assert_eq!(commits[0].proof.round, 0);
+        assert_eq!(commits[0].block, network.commits[1][0].block);
+        assert_eq!(commits[0].address, byzantine);
+    }
+    assert_ne!(network.commits[0][0].block, b"fake block".to_vec().into());
+    assert_eq!(network.commits[0][0].evidences.len(), 1);
+}
+
+#[test]
+fn test_engine_commit_without_round_proposal() {
+    // node 3 is down and precommits nil in round 0, while the precommits of node 1 and
+    // those of node 2 to node 1 are lost, so the nodes lock the block in round 0 but go to
+    // round 1 without a commit
+    let config = test_config(WalDurability::NoSync);
+    let mut network = Network::with_election(config, None, Some(3), &[1; NODE_NUM]);
+    let nil_precommit = signed_vote(1, 1, 0, &Hash::default(), &vec![3].into(), &vec![3].into());
+    let mut injected = Vec::new();
+    while network.commits[..3]
+        .iter()
+        .any(|commits| commits.is_empty())
+    {
+        let (j, msg) = network.inbox.front().cloned().unwrap_or((3, BftMsg::Start));
+        match msg {
+            BftMsg::Vote(encode) => {
+                let vote = Rlp::new(&encode).at(0).unwrap();
+                let voter: Address = vote.val_at(4).unwrap();
+                if vote.val_at::<u8>(0).unwrap() == 1 && vote.val_at::<Round>(2).unwrap() == 0 {
+                    if !injected.contains(&j) {
+                        injected.push(j);
+                        network
+                            .inbox
+                            .push_front((j, BftMsg::Vote(nil_precommit.clone())));
+                    } else if voter == vec![1].into() || (voter == vec![2].into() && j == 1) {
+                        network.inbox.pop_front();
+                        continue;
+                    }
+                }
+            }
+            // the proposals of the later rounds are lost
+            BftMsg::Proposal(encode) => {
+                let (proposal, _) = extract_two(&encode).unwrap();
+                if Rlp::new(proposal)
+                    .at(0)
+                    .unwrap()
+                    .val_at::<Round>(1)
+                    .unwrap()
+                    > 0
+                {
+                    network.inbox.pop_front();
+                    continue;
+                }
+            }
+            _ => {}
+        }
+        network.step();
+    }
+
+    // the proposer of the locked block in round 0 gets the commit, not the one of round 1
+    for commits in network.commits[..3].iter() {
+        assert_eq!(commits[0].proof.round, 1);
+        assert_eq!(commits[0].block, network.commits[0][0].block);
+        assert_eq!(commits[0].address, vec![1].into());
+    }
+}
+
+#[test]
+fn test_engine_misbehavior() {
+    let mut network = Network::new();
+    while network.engines[0].state().height < 1 {
+        network.step();
+    }
+    let block_hash: Hash = Sha256::digest(b"block").to_vec().into();
+    // a vote of node 2 signed by node 3, and a vote of a node not in the authorities
+    let forged_vote = signed_vote(0, 1, 0, &block_hash, &vec![2].into(), &vec![3].into());
+    let stranger_vote = signed_vote(0, 1, 0, &block_hash, &vec![9].into(), &vec![8].into());
+    // node 1 is the proposer of height 1 round 4, whose proposal is locked by one vote only
+    let lock_vote = signed_vote(0, 1, 4, &block_hash, &vec![2].into(), &vec![2].into());
+    let proposal = signed_proposal(1, 4, b"block", Some(4), &[lock_vote], &vec![1].into());
+    network.inbox.push_front((0, BftMsg::Proposal(proposal)));
+    network.inbox.push_front((0, BftMsg::Vote(stranger_vote)));
+    network
+        .inbox
+        .push_front((0, BftMsg::Vote(forged_vote.clone())));
+    network.run_to(2);
+
+    let misbehaviors = network.misbehaviors[0].lock().unwrap();
+    assert_eq!(misbehaviors.len(), 2);
+    assert_eq!(
+        misbehaviors[0],
+        Misbehavior::InvalidSignature {
+            address: vec![2].into(),
+            signed_msg: forged_vote,
+        }
+    );
+    match &misbehaviors[1] {
+        Misbehavior::InvalidLockVotes { address, .. } => assert_eq!(address, &vec![1].into()),
+        misbehavior => panic!("unexpected {:?}", misbehavior),
+    }
+    for misbehaviors in network.misbehaviors.iter().skip(1) {
+        assert!(misbehaviors.lock().unwrap().is_empty());
+    }
+}
+
+#[test]
+fn test_engine_wal_durability() {
+    // the own proposals and votes are synced before they are broadcast
+    for durability in [
+        WalDurability::EveryRecord,
+        WalDurability::StepTransition,
+        WalDurability::GroupCommit(1_000),
+    ] {
+        let mut network = Network::with_config(test_config(durability));
+        network.check_synced = true;
+        network.run_to(3);
+    }
+
+    // the status is synced by itself, or by the step change to CommitWait
+    for durability in [WalDurability::EveryRecord, WalDurability::StepTransition] {
+        let mut network = Network::with_config(test_config(durability));
+        network.step();
+        network.step();
+        assert_eq!(network.engines[0].state().step, Step::CommitWait);
+        assert_eq!(network.storages[0].unsynced(), 0);
+    }
+
+    // the last records of a group commit are synced by a timer
+    let mut network = Network::with_config(test_config(WalDurability::GroupCommit(1_000)));
+    network.step();
+    network.step();
+    assert!(network.storages[0].unsynced() > 0);
+    let (_, timeout) = network
+        .timers
+        .iter()
+        .filter(|(i, _)| *i == 0)
+        .min_by_key(|(_, timeout)| timeout.get_instant())
+        .cloned()
+        .unwrap();
+    network.engines[0].handle_timeout(timeout);
+    assert_eq!(network.storages[0].unsynced(), 0);
+    assert_eq!(network.engines[0].state().step, Step::CommitWait);
+
+    // a group commit follows the clock of the engine
+    let mut network = Network::with_config(test_config(WalDurability::GroupCommit(1_000)));
+    network.clock.advance(Duration::from_millis(1_000));
+    network.step();
+    network.step();
+    let synced = network.storages[0].synced.lock().unwrap().clone();
+    assert!(synced.values().any(|len| *len > 0));
+
+    let mut network = Network::with_config(test_config(WalDurability::NoSync));
+    network.run_to(3);
+    for storage in network.storages.iter() {
+        assert!(storage.unsynced() > 0);
+        assert!(storage.synced.lock().unwrap().values().all(|len| *len == 0));
+    }
+}
+
+#[test]
+fn test_engine_restart_vote() {
+    // node 3 is down, so no height is committed without node 0
+    let config = test_config(WalDurability::NoSync);
+    let mut network = Network::with_election(config, None, Some(3), &[1; NODE_NUM]);
+    // hold the proposals to node 0 until it prevotes nil in height 1 round 0
+    let mut held = Vec::new();
+    while network.broadcast_votes(0, 0, 1, 0).is_empty() {
+        match network.inbox.front() {
+            Some((0, BftMsg::Proposal(_))) => held.push(network.inbox.pop_front().unwrap()),
+            _ => network.step(),
+        }
+    }
+    network.restart(0);
+    network.inbox.extend(held);
+    while network.engines[0].state().height < 2 {
+        network.step();
+    }
+
+    // a lagging vote of the commit round makes node 0 retransmit its votes of height 1
+    let round = network.commits[0][0].proof.round;
+    let sent = network.broadcast_votes(0, 0, 1, round).len();
+    let lagging_vote = signed_vote(
+        1,
+        1,
+        round,
+        &Hash::default(),
+        &vec![3].into(),
+        &vec![3].into(),
+    );
+    network.inbox.push_front((0, BftMsg::Vote(lagging_vote)));
+    network.step();
+    assert!(network.broadcast_votes(0, 0, 1, round).len() > sent);
+
+    // node 0 never signs another vote in a round, whether it restarts or retransmits
+    let nil_prevote = signed_vote(0, 1, 0, &Hash::default(), &vec![0].into(), &vec![0].into());
+    assert!(network
+        .broadcast_votes(0, 0, 1, 0)
+        .iter()
+        .all(|prevote| *prevote == nil_prevote));
+    for r in 0..=round {
+        for vote_type in 0..2 {
+            let votes = network.broadcast_votes(0, vote_type, 1, r);
+            assert!(votes.iter().all(|vote| *vote == votes[0]));
+        }
+    }
+    network.run_to(3);
+    for commits in network.commits.iter() {
+        assert!(commits.iter().all(|commit| commit.evidences.is_empty()));
+    }
+}
+
+#[test]
+fn test_engine_reproducible() {
+    let mut first = Network::new();
+    first.run_to(5);
+    let mut second = Network::new();
+    second.run_to(5);
+
+    assert_eq!(first.commits, second.commits);
+    assert_eq!(first.clock.elapsed(), second.clock.elapsed());
+}
+
+#[test]
+fn test_engine_evidence() {
+    let mut network = Network::new();
+    let byzantine: Address = vec![3].into();
+    let fake_hash: Hash = Sha256::digest(b"fake block").to_vec().into();
+    // node 3 signs another prevote of height 1, which arrives before its real one
+    let prevote = signed_vote(0, 1, 0, &fake_hash, &byzantine, &byzantine);
+    for i in 0..3 {
+        network.inbox.push_back((i, BftMsg::Vote(prevote.clone())));
+    }
+    while network.commits[0].is_empty() {
+        network.step();
+    }
+    // and another precommit of height 1, which arrives after node 0 commits height 1
+    assert_eq!(network.engines[0].state().height, 1);
+    let precommit = signed_vote(1, 1, 0, &fake_hash, &byzantine, &byzantine);
+    network.inbox.push_back((0, BftMsg::Vote(precommit)));
+    network.run_to(3);
+
+    for (i, commits) in network.commits.iter().take(3).enumerate() {
+        let evidences: Vec<(Height, &Evidence)> = commits
+            .iter()
+            .flat_map(|commit| commit.evidences.iter().map(move |e| (commit.height, e)))
+            .collect();
+        // each evidence is committed once, a late evidence in the next commit
+        assert_eq!(evidences.len(), if i == 0 { 2 } else { 1 });
+        let (height, evidence) = evidences[0];
+        assert_eq!(height, 1);
+        assert_eq!(evidence.evidence_type, EvidenceType::DuplicatePrevote);
+        if i == 0 {
+            let (height, evidence) = evidences[1];
+            assert_eq!(height, 2);
+            assert_eq!(evidence.evidence_type, EvidenceType::DuplicatePrecommit);
+        }
+        for (_, evidence) in evidences {
+            assert_eq!((evidence.height, evidence.round), (1, 0));
+            assert_eq!(evidence.address, byzantine);
+            assert!(check_evidence(
+                evidence,
+                |msg| Sha256::digest(msg).to_vec().into(),
+                |signature, _| Some(signature.to_vec().into()),
+            ));
+        }
+    }
+}
+
+#[test]
+fn test_engine_wal_load() {
+    let status = wal_record(2, &rlp::encode(&Network::new().status(0)));
+    // replay the wal log of height 1, return the engine and the wal log after the replay
+    let replay = |log: &[u8]| {
+        let mut storage = MemoryWalStorage::new();
+        storage.set_current_height(1).unwrap();
+        storage.append(1, log).unwrap();
+        let address: Address = vec![0].into();
+        let support = Arc::new(Support {
+            address: address.clone(),
+            misbehaviors: Arc::default(),
+        });
+        let config = BftConfig::builder().build().unwrap();
+        let clock = Arc::new(MockClock::new());
+        let engine = BftEngine::new(support, address, storage.clone(), config, clock);
+        (engine, storage.load(1).unwrap())
+    };
+
+    // a clean end, the status is replayed and the node goes to height 1
+    let (engine, log) = replay(&status);
+    assert_eq!(engine.unwrap().state().height, 1);
+    assert_eq!(log, status);
+
+    // a torn tail is truncated
+    let mut torn = status.clone();
+    torn.extend_from_slice(&status[..status.len() - 1]);
+    let (engine, log) = replay(&torn);
+    assert_eq!(engine.unwrap().state().height, 1);
+    assert_eq!(log, status);
+
+    // the last record partially written can not pass the crc check, which is torn as well
+    let mut torn = status.clone();
+    *torn.last_mut().unwrap() ^= 1;
+    let (engine, log) = replay(&torn);
+    assert_eq!(engine.unwrap().state().height, 0);
+    assert!(log.is_empty());
+
+    // a corrupted record followed by other records is an error, and the log is kept
+    let mut corrupted = torn;
+    corrupted.extend_from_slice(&status);
+    let (engine, log) = replay(&corrupted);
+    assert!(engine.is_err());
+    assert_eq!(log, corrupted);
+}
@@ -0,0 +1,141 @@
+use bft_rs::*;
+use rlp::RlpStream;
+use sha2::{Digest, Sha256};
+
+const PREVOTE: u8 = 0;
+const PRECOMMIT: u8 = 1;
+
+fn crypt_hash(msg: &[u8]) -> Hash {
+    Sha256::digest(msg).to_vec().into()
+}
+
+// the signature of a test signer is its address
+fn check_sig(signature: &Signature, _hash: &Hash) -> Option<Address> {
+    Some(signature.to_vec().into())
+}
+
+/// Encode a signed vote the same as in BftMsg::Vote.
+fn signed_vote(
+    vote_type: u8,
+    height: Height,
+    round: Round,
+    block_hash: &[u8],
+    voter: &Address,
+    signer: &Address,
+) -> Vec<u8> {
+    let mut vote = RlpStream::new_list(5);
+    vote.append(&vote_type)
+        .append(&height)
+        .append(&round)
+        .append(&Hash::from(block_hash))
+        .append(voter);
+    let mut signed_vote = RlpStream::new_list(2);
+    signed_vote
+        .append_raw(&vote.out(), 1)
+        .append(&Signature::from(signer.to_vec()));
+    signed_vote.out()
+}
+
+fn vote_evidence(first: Vec<u8>, second: Vec<u8>) -> Evidence {
+    Evidence {
+        evidence_type: EvidenceType::DuplicatePrevote,
+        height: 3,
+        round: 1,
+        address: Address::from(vec![2]),
+        first,
+        second,
+    }
+}
+
+#[test]
+fn test_evidence_encoding() {
+    let voter = Address::from(vec![2]);
+    let evidence = vote_evidence(
+        signed_vote(PREVOTE, 3, 1, b"a", &voter, &voter),
+        signed_vote(PREVOTE, 3, 1, b"b", &voter, &voter),
+    );
+    let encode = rlp::encode(&evidence);
+    assert_eq!(rlp::decode::<Evidence>(&encode), Ok(evidence));
+
+    // an unknown evidence type is rejected instead of panicking
+    let mut s = RlpStream::new_list(6);
+    s.append(&9u8)
+        .append(&3u64)
+        .append(&1u64)
+        .append(&voter)
+        .append(&vec![1u8])
+        .append(&vec![2u8]);
+    assert!(rlp::decode::<Evidence>(&s.out()).is_err());
+}
+
+#[test]
+fn test_commit_encoding() {
+    let voter = Address::from(vec![2]);
+    let commit = Commit {
+        height: 3,
+        block: Block::from(vec![1, 2, 3]),
+        proof: Proof::default(),
+        address: Address::from(vec![1]),
+        evidences: vec![vote_evidence(
+            signed_vote(PREVOTE, 3, 1, b"a", &voter, &voter),
+            signed_vote(PREVOTE, 3, 1, b"b", &voter, &voter),
+        )],
+    };
+    let decoded: Commit = rlp::decode(&rlp::encode(&commit)).unwrap();
+    assert_eq!(decoded.height, commit.height);
+    assert_eq!(decoded.block, commit.block);
+    assert_eq!(decoded.address, commit.address);
+    assert_eq!(decoded.evidences, commit.evidences);
+
+    // a commit encoded before the evidences were added
+    let mut s = RlpStream::new_list(4);
+    s.append(&commit.height)
+        .append(&commit.block)
+        .append(&commit.proof)
+        .append(&commit.address);
+    let decoded: Commit = rlp::decode(&s.out()).unwrap();
+    assert_eq!(decoded.height, commit.height);
+    assert!(decoded.evidences.is_empty());
+}
+
+#[test]
+fn test_check_vote_evidence() {
+    let voter = Address::from(vec![2]);
+    let other = Address::from(vec![3]);
+    let check =
+        |first, second| check_evidence(&vote_evidence(first, second), crypt_hash, check_sig);
+
+    assert!(check(
+        signed_vote(PREVOTE, 3, 1, b"a", &voter, &voter),
+        signed_vote(PREVOTE, 3, 1, b"b", &voter, &voter),
+    ));
+    // a nil vote conflicts with a vote for a block
+    assert!(check(
+        signed_vote(PREVOTE, 3, 1, b"", &voter, &voter),
+        signed_vote(PREVOTE, 3, 1, b"b", &voter, &voter),
+    ));
+    // the same vote twice is not a misbehavior
+    assert!(!check(
+        signed_vote(PREVOTE, 3, 1, b"a", &voter, &voter),
+        signed_vote(PREVOTE, 3, 1, b"a", &voter, &voter),
+    ));
+    // votes of different rounds, types or voters do not conflict
+    assert!(!check(
+        signed_vote(PREVOTE, 3, 1, b"a", &voter, &voter),
+        signed_vote(PREVOTE, 3, 2, b"b", &voter, &voter),
+    ));
+    assert!(!check(
+        signed_vote(PREVOTE, 3, 1, b"a", &voter, &voter),
+        signed_vote(PRECOMMIT, 3, 1, b"b", &voter, &voter),
+    ));
+    assert!(!check(
+        signed_vote(PREVOTE, 3, 1, b"a", &voter, &voter),
+        signed_vote(PREVOTE, 3, 1, b"b", &other, &other),
+    ));
+    // a vote not signed by the voter proves nothing
+    assert!(!check(
+        signed_vote(PREVOTE, 3, 1, b"a", &voter, &voter),
+        signed_vote(PREVOTE, 3, 1, b"b", &voter, &other),
+    ));
+    assert!(!check(vec![1, 2, 3], vec![4, 5, 6]));
+}
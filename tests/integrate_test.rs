@@ -2,25 +2,130 @@ pub mod common;
 
 use crate::common::config::{BAD_CONFIG, NORMAL_CONFIG, PERFECT_CONFIG};
 use crate::common::env::{Content, Env};
+use crate::common::support::Support;
 use crate::common::utils::{
     clean_log_file, clean_wal, get_random_integer, set_log_file, RandomMode,
 };
+use bft_rs::error::BftError;
+use bft_rs::objects::Step;
+use bft_rs::wal::{FileWalStorage, WalStorage};
+use bft_rs::{BftActuator, BftEvent, BftMsg};
+use crossbeam::crossbeam_channel::unbounded;
 #[allow(unused_imports)]
 use log::{info, log, LevelFilter};
 use std::collections::HashMap;
+use std::fs;
+use std::sync::Arc;
 use std::time::Duration;
 
 #[test]
 fn test_basic() {
     let path = "log/test_basic.log";
-    let wal_dir = "wal/test_basic/wal";
-    clean_wal(wal_dir);
     clean_log_file(path);
     set_log_file(path, LevelFilter::Debug);
-    let mut env = Env::new(PERFECT_CONFIG, 4, wal_dir);
+    let mut env = Env::new_with_memory_wal(PERFECT_CONFIG, 4);
     env.run(10);
 }
 
+#[test]
+fn test_query_state() {
+    let env = Env::new_with_memory_wal(PERFECT_CONFIG, 4);
+    for actuator in env.live_nodes.values() {
+        let state = actuator.query_state().unwrap();
+        assert_eq!(state.height, 0);
+        assert!(state.authority_list.is_empty());
+
+        // a status of a higher height brings the node to the next height at once
+        let mut status = env.status.clone();
+        status.height = 1;
+        actuator.send(BftMsg::Status(status)).unwrap();
+        let state = actuator.query_state().unwrap();
+        assert_eq!(state.height, 2);
+        assert_eq!(state.round, 0);
+        assert_eq!(state.step, Step::ProposeWait);
+        assert_eq!(state.lock_round, None);
+        assert_eq!(state.authority_list.len(), 4);
+    }
+}
+
+#[test]
+fn test_shutdown() {
+    let mut env = Env::new_with_memory_wal(PERFECT_CONFIG, 4);
+    for actuator in env.live_nodes.values_mut() {
+        actuator.send(BftMsg::Start).unwrap();
+        actuator.shutdown().unwrap();
+        // the state machine has stopped
+        assert!(actuator.send(BftMsg::Start).is_err());
+        assert!(actuator.query_state().is_err());
+        // shutting down again does nothing
+        actuator.shutdown().unwrap();
+    }
+}
+
+#[test]
+fn test_subscribe() {
+    let env = Env::new_with_memory_wal(PERFECT_CONFIG, 4);
+    for actuator in env.live_nodes.values() {
+        let events = actuator.subscribe().unwrap();
+        let mut status = env.status.clone();
+        status.height = 1;
+        actuator.send(BftMsg::Status(status)).unwrap();
+
+        let timeout = Duration::from_secs(1);
+        assert_eq!(
+            events.recv_timeout(timeout),
+            Ok(BftEvent::NewHeight { height: 2 })
+        );
+        assert_eq!(
+            events.recv_timeout(timeout),
+            Ok(BftEvent::NewRound {
+                height: 2,
+                round: 0
+            })
+        );
+        assert_eq!(
+            events.recv_timeout(timeout),
+            Ok(BftEvent::StepChange {
+                height: 2,
+                round: 0,
+                step: Step::ProposeWait
+            })
+        );
+    }
+}
+
+#[test]
+fn test_start_failure() {
+    let (msg_send, _msg_recv) = unbounded();
+    let (commit_send, _commit_recv) = unbounded();
+    let support = Arc::new(Support {
+        config: PERFECT_CONFIG,
+        address: vec![0].into(),
+        msg_send,
+        commit_send,
+    });
+    let _ = fs::remove_dir_all("wal/test_start_failure");
+
+    // the wal directory is under a file
+    fs::create_dir_all("wal/test_start_failure").unwrap();
+    fs::write("wal/test_start_failure/file", b"").unwrap();
+    let result = BftActuator::new(
+        support.clone(),
+        vec![0].into(),
+        "wal/test_start_failure/file/wal",
+    );
+    assert!(matches!(result, Err(BftError::WalDirErr(_))));
+
+    // a corrupted record followed by another record
+    let dir = "wal/test_start_failure/wal";
+    let mut storage = FileWalStorage::new(dir).unwrap();
+    storage.append(1, &[1, 0, 0, 0, 0, 0, 0, 0, 0, 0]).unwrap();
+    storage.append(1, &[0; 10]).unwrap();
+    storage.set_current_height(1).unwrap();
+    let result = BftActuator::new(support, vec![0].into(), dir);
+    assert!(matches!(result, Err(BftError::LoadWalErr(_))));
+}
+
 #[test]
 fn test_restart() {
     let path = "log/test_restart.log";
@@ -45,6 +150,35 @@ fn test_restart() {
     env.run(100);
 }
 
+#[test]
+fn test_restart_mid_round() {
+    let path = "log/test_restart_mid_round.log";
+    clean_log_file(path);
+    set_log_file(path, LevelFilter::Debug);
+    let mut env = Env::new_with_memory_wal(NORMAL_CONFIG, 4);
+
+    // kill nodes at different points of a round, the proposals must be replayed from the wal
+    env.set_node(0, Content::Stop, Duration::from_millis(1_700));
+    env.set_node(0, Content::Start(0), Duration::from_millis(2_300));
+    env.set_node(1, Content::Stop, Duration::from_millis(5_150));
+    env.set_node(1, Content::Start(1), Duration::from_millis(5_900));
+    env.set_node(2, Content::Stop, Duration::from_millis(8_050));
+    env.set_node(3, Content::Stop, Duration::from_millis(8_100));
+    env.set_node(2, Content::Start(2), Duration::from_millis(9_400));
+    env.set_node(3, Content::Start(3), Duration::from_millis(9_450));
+    // stop all nodes in the middle of a round, start all nodes
+    for i in 0..4 {
+        env.set_node(i, Content::Stop, Duration::from_millis(13_333));
+        env.set_node(
+            i,
+            Content::Start(i),
+            Duration::from_millis(14_000 + 500 * i as u64),
+        );
+    }
+
+    env.run(30);
+}
+
 #[test]
 fn test_wild() {
     let path = "log/test_wild.log";
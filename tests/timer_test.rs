@@ -0,0 +1,43 @@
+use bft_rs::clock::{Clock, MockClock};
+use bft_rs::timer::{GetInstant, WaitTimer};
+use crossbeam::crossbeam_channel::unbounded;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd)]
+struct Timeout(Instant);
+
+impl GetInstant for Timeout {
+    fn get_instant(&self) -> Instant {
+        self.0
+    }
+}
+
+#[test]
+fn test_mock_clock_timer() {
+    let clock = MockClock::new();
+    let (test2timer, timer4test) = unbounded();
+    let (timer2test, test4timer) = unbounded();
+    let timer_clock: Arc<dyn Clock> = Arc::new(clock.clone());
+    thread::spawn(move || {
+        let timer = WaitTimer::with_clock(timer2test, timer4test, timer_clock);
+        timer.start();
+    });
+
+    let first = Timeout(clock.now() + Duration::from_secs(3));
+    let second = Timeout(clock.now() + Duration::from_secs(60));
+    test2timer.send(second.clone()).unwrap();
+    test2timer.send(first.clone()).unwrap();
+
+    // nothing fires until the virtual time reaches the deadline
+    assert!(test4timer.recv_timeout(Duration::from_millis(50)).is_err());
+    clock.advance(Duration::from_millis(2_999));
+    assert!(test4timer.recv_timeout(Duration::from_millis(50)).is_err());
+    clock.advance(Duration::from_millis(1));
+    assert_eq!(test4timer.recv_timeout(Duration::from_secs(1)), Ok(first));
+
+    clock.advance(Duration::from_secs(57));
+    assert_eq!(test4timer.recv_timeout(Duration::from_secs(1)), Ok(second));
+    assert_eq!(clock.elapsed(), Duration::from_secs(60));
+}
@@ -0,0 +1,316 @@
+use bft_rs::inspect::inspect_wal_dir;
+use bft_rs::wal::{FileWalStorage, MemoryWalStorage, WalStorage};
+use crc32fast::Hasher as Crc32;
+use flate2::read::GzDecoder;
+use std::fs;
+use std::io::{self, Read};
+
+const VOTE: u8 = 1;
+const STATUS: u8 = 2;
+const PROOF: u8 = 3;
+
+fn new_storage(dir: &str) -> FileWalStorage {
+    let _ = fs::remove_dir_all(dir);
+    FileWalStorage::new(dir).unwrap()
+}
+
+/// Frame a record the same as the wal log, whose header is
+/// [len u32][type u8][crc32 u32][header crc32 u32].
+fn record(log_type: u8, msg: &[u8]) -> Vec<u8> {
+    let len = (msg.len() as u32).to_le_bytes();
+    let mut crc = Crc32::new();
+    crc.update(&len);
+    crc.update(&[log_type]);
+    crc.update(msg);
+    let mut record = len.to_vec();
+    record.push(log_type);
+    record.extend_from_slice(&crc.finalize().to_le_bytes());
+    let mut header_crc = Crc32::new();
+    header_crc.update(&record);
+    record.extend_from_slice(&header_crc.finalize().to_le_bytes());
+    record.extend_from_slice(msg);
+    record
+}
+
+/// Frame a record the same as a wal file of version 1, whose header is [len u32][type u8][crc32 u32].
+fn v1_record(log_type: u8, msg: &[u8]) -> Vec<u8> {
+    let mut crc = Crc32::new();
+    crc.update(&[log_type]);
+    crc.update(msg);
+    let mut record = (msg.len() as u32).to_le_bytes().to_vec();
+    record.push(log_type);
+    record.extend_from_slice(&crc.finalize().to_le_bytes());
+    record.extend_from_slice(msg);
+    record
+}
+
+/// Frame a record the same as a headerless wal file of version 0, whose header is [len u32][type u8].
+fn legacy_record(log_type: u8, msg: &[u8]) -> Vec<u8> {
+    let mut record = (msg.len() as u32).to_le_bytes().to_vec();
+    record.push(log_type);
+    record.extend_from_slice(msg);
+    record
+}
+
+/// Write a wal file with the header of the version.
+fn write_file(path: &str, version: u32, records: &[u8]) {
+    let mut buf = b"BWAL".to_vec();
+    buf.extend_from_slice(&version.to_le_bytes());
+    buf.extend_from_slice(records);
+    fs::write(path, buf).unwrap();
+}
+
+#[test]
+fn test_wal_index() {
+    let dir = "wal/test_wal_index";
+    let mut storage = new_storage(dir);
+    assert_eq!(storage.current_height().unwrap(), 0);
+
+    storage.set_current_height(7).unwrap();
+    assert_eq!(storage.current_height().unwrap(), 7);
+    assert_eq!(fs::read_to_string(format!("{}/index", dir)).unwrap(), "7");
+
+    let mut storage = FileWalStorage::new(dir).unwrap();
+    assert_eq!(storage.current_height().unwrap(), 7);
+}
+
+#[test]
+fn test_wal_index_recovery() {
+    let dir = "wal/test_wal_index_recovery";
+    let mut storage = new_storage(dir);
+    storage.append(5, &record(STATUS, b"h4")).unwrap();
+    storage.append(6, &record(STATUS, b"h5")).unwrap();
+    // the msgs of the next height are saved before its status
+    storage.append(7, &record(VOTE, b"h7")).unwrap();
+    storage.set_current_height(6).unwrap();
+
+    // a corrupt index
+    fs::write(format!("{}/index", dir), b"6x").unwrap();
+    let mut storage = FileWalStorage::new(dir).unwrap();
+    assert_eq!(storage.current_height().unwrap(), 6);
+    assert_eq!(fs::read_to_string(format!("{}/index", dir)).unwrap(), "6");
+
+    // a missing index
+    fs::remove_file(format!("{}/index", dir)).unwrap();
+    let mut storage = FileWalStorage::new(dir).unwrap();
+    assert_eq!(storage.current_height().unwrap(), 6);
+    assert_eq!(storage.load(6).unwrap(), record(STATUS, b"h5"));
+}
+
+#[test]
+fn test_wal_archive() {
+    let dir = "wal/test_wal_archive/wal";
+    let archive_dir = "wal/test_wal_archive/archive";
+    let _ = fs::remove_dir_all("wal/test_wal_archive");
+    let mut storage = FileWalStorage::with_archive(dir, archive_dir).unwrap();
+    for height in 1..=4 {
+        storage.append(height, &[height as u8; 16]).unwrap();
+    }
+
+    storage.truncate_below(3).unwrap();
+    assert!(fs::metadata(format!("{}/2.log", dir)).is_err());
+    assert_eq!(storage.load(3).unwrap(), vec![3u8; 16]);
+
+    let mut decoder = GzDecoder::new(fs::File::open(format!("{}/2.log.gz", archive_dir)).unwrap());
+    let mut archived = Vec::new();
+    decoder.read_to_end(&mut archived).unwrap();
+    assert_eq!(&archived[archived.len() - 16..], &[2u8; 16]);
+
+    // the same height archived again does not overwrite the former archive
+    storage.append(2, &[5u8; 16]).unwrap();
+    storage.truncate_below(3).unwrap();
+    assert!(fs::metadata(format!("{}/2.log.gz", archive_dir)).is_ok());
+    let mut decoder =
+        GzDecoder::new(fs::File::open(format!("{}/2.log.1.gz", archive_dir)).unwrap());
+    let mut archived = Vec::new();
+    decoder.read_to_end(&mut archived).unwrap();
+    assert_eq!(&archived[archived.len() - 16..], &[5u8; 16]);
+
+    // each clear is kept in its own directory, even if they are at once
+    storage.clear().unwrap();
+    assert!(storage.load(4).unwrap().is_empty());
+    storage.append(4, &[6u8; 16]).unwrap();
+    storage.clear().unwrap();
+    let cleared: Vec<_> = fs::read_dir(archive_dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.is_dir())
+        .collect();
+    assert_eq!(cleared.len(), 2);
+    assert!(cleared.iter().all(|dir| dir.join("4.log.gz").is_file()));
+}
+
+#[test]
+fn test_wal_memory() {
+    let mut storage = MemoryWalStorage::new();
+    assert_eq!(storage.current_height().unwrap(), 0);
+    assert!(storage.load(1).unwrap().is_empty());
+
+    // the records are appended to the log of their height
+    for height in 1..=4 {
+        storage.append(height, &[height as u8; 4]).unwrap();
+        storage.append(height, &[0u8; 2]).unwrap();
+    }
+    storage.set_current_height(4).unwrap();
+    let mut log = vec![3u8; 4];
+    log.extend_from_slice(&[0u8; 2]);
+    assert_eq!(storage.load(3).unwrap(), log);
+
+    storage.set_len(3, 4).unwrap();
+    assert_eq!(storage.load(3).unwrap(), vec![3u8; 4]);
+
+    // the logs below the height are removed, the clones share the logs
+    let mut clone = storage.clone();
+    clone.truncate_below(3).unwrap();
+    assert!(storage.load(1).unwrap().is_empty());
+    assert!(storage.load(2).unwrap().is_empty());
+    assert_eq!(storage.load(3).unwrap(), vec![3u8; 4]);
+    assert_eq!(storage.load(4).unwrap().len(), 6);
+    assert_eq!(storage.current_height().unwrap(), 4);
+
+    clone.clear().unwrap();
+    assert!(storage.load(4).unwrap().is_empty());
+    assert_eq!(storage.current_height().unwrap(), 0);
+}
+
+#[test]
+fn test_wal_inspect() {
+    let dir = "wal/test_wal_inspect";
+    let mut storage = new_storage(dir);
+    storage.append(2, &[0u8; 3]).unwrap();
+    storage.set_current_height(2).unwrap();
+
+    let dump = inspect_wal_dir(dir).unwrap();
+    assert_eq!(dump.current_height, Some(2));
+    assert_eq!(dump.files.len(), 1);
+    assert!(dump.files[0].records.is_empty());
+    assert_eq!(dump.files[0].error.as_deref(), Some("torn tail of 3 bytes"));
+    assert_eq!(dump.to_json()["files"][0]["height"], 2);
+}
+
+#[test]
+fn test_wal_legacy_file() {
+    let dir = "wal/test_wal_legacy_file";
+    let _ = fs::remove_dir_all(dir);
+    fs::create_dir_all(dir).unwrap();
+    let mut legacy = legacy_record(STATUS, b"status");
+    legacy.extend_from_slice(&legacy_record(PROOF, b"proof"));
+    // a torn tail
+    legacy.extend_from_slice(&legacy_record(PROOF, b"proof")[..7]);
+    fs::write(format!("{}/1.log", dir), &legacy).unwrap();
+    fs::write(format!("{}/index", dir), "1").unwrap();
+
+    // the headerless file is version 0, which is rewritten in the current format
+    let mut storage = FileWalStorage::new(dir).unwrap();
+    assert_eq!(storage.current_height().unwrap(), 1);
+    let mut expect = record(STATUS, b"status");
+    expect.extend_from_slice(&record(PROOF, b"proof"));
+    assert_eq!(storage.load(1).unwrap(), expect);
+    assert!(fs::read(format!("{}/1.log", dir))
+        .unwrap()
+        .starts_with(b"BWAL"));
+
+    storage.append(1, &record(STATUS, b"more")).unwrap();
+    let dump = inspect_wal_dir(dir).unwrap();
+    assert_eq!(dump.files[0].records.len(), 3);
+    assert_eq!(dump.files[0].error, None);
+}
+
+#[test]
+fn test_wal_decode() {
+    let dir = "wal/test_wal_decode";
+    let _ = fs::remove_dir_all(dir);
+    fs::create_dir_all(dir).unwrap();
+    let mut records = record(STATUS, b"status");
+    records.extend_from_slice(&record(PROOF, b"proof"));
+
+    // a clean end
+    write_file(&format!("{}/1.log", dir), 2, &records);
+    // a crc mismatch in the middle of the file
+    let mut corrupted = records.clone();
+    corrupted[10] ^= 1;
+    write_file(&format!("{}/2.log", dir), 2, &corrupted);
+    // an unknown version
+    write_file(&format!("{}/3.log", dir), 3, &records);
+
+    let dump = inspect_wal_dir(dir).unwrap();
+    assert_eq!(dump.files.len(), 3);
+    assert_eq!(dump.files[0].records.len(), 2);
+    assert_eq!(dump.files[0].error, None);
+    assert!(dump.files[1].records.is_empty());
+    assert_eq!(
+        dump.files[1].error.as_deref(),
+        Some("wal log of height 2 is corrupted at offset 0")
+    );
+    assert!(dump.files[2].records.is_empty());
+    assert!(dump.files[2]
+        .error
+        .as_deref()
+        .unwrap()
+        .ends_with("has unsupported version 3"));
+
+    let mut storage = FileWalStorage::new(dir).unwrap();
+    assert_eq!(storage.load(1).unwrap(), records);
+    let error = storage.load(3).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn test_wal_decode_length() {
+    let dir = "wal/test_wal_decode_length";
+    let _ = fs::remove_dir_all(dir);
+    fs::create_dir_all(dir).unwrap();
+    let mut records = record(STATUS, b"status");
+    records.extend_from_slice(&record(PROOF, b"proof"));
+    records.extend_from_slice(&record(PROOF, b"proof"));
+
+    // a flipped bit of the length of a record in the middle is not a torn tail
+    let mut corrupted = records.clone();
+    corrupted[19 + 2] ^= 1;
+    write_file(&format!("{}/1.log", dir), 2, &corrupted);
+    // a short last record is a torn tail
+    write_file(&format!("{}/2.log", dir), 2, &records[..records.len() - 2]);
+    // a zeroed tail of a torn write
+    let mut zeroed = records.clone();
+    zeroed.extend_from_slice(&[0u8; 20]);
+    write_file(&format!("{}/3.log", dir), 2, &zeroed);
+
+    let dump = inspect_wal_dir(dir).unwrap();
+    assert_eq!(dump.files[0].records.len(), 1);
+    assert_eq!(
+        dump.files[0].error.as_deref(),
+        Some("wal log of height 1 is corrupted at offset 19")
+    );
+    assert_eq!(dump.files[1].records.len(), 2);
+    assert_eq!(
+        dump.files[1].error.as_deref(),
+        Some("torn tail of 16 bytes")
+    );
+    assert_eq!(dump.files[2].records.len(), 3);
+    assert_eq!(
+        dump.files[2].error.as_deref(),
+        Some("torn tail of 20 bytes")
+    );
+}
+
+#[test]
+fn test_wal_v1_file() {
+    let dir = "wal/test_wal_v1_file";
+    let _ = fs::remove_dir_all(dir);
+    fs::create_dir_all(dir).unwrap();
+    let mut v1 = v1_record(STATUS, b"status");
+    v1.extend_from_slice(&v1_record(PROOF, b"proof"));
+    // a torn tail
+    v1.extend_from_slice(&v1_record(PROOF, b"proof")[..11]);
+    write_file(&format!("{}/1.log", dir), 1, &v1);
+    fs::write(format!("{}/index", dir), "1").unwrap();
+
+    // the file of version 1 is rewritten in the current format
+    let mut storage = FileWalStorage::new(dir).unwrap();
+    let mut expect = record(STATUS, b"status");
+    expect.extend_from_slice(&record(PROOF, b"proof"));
+    assert_eq!(storage.load(1).unwrap(), expect);
+    let dump = inspect_wal_dir(dir).unwrap();
+    assert_eq!(dump.files[0].records.len(), 2);
+    assert_eq!(dump.files[0].error, None);
+}